@@ -0,0 +1,107 @@
+//! `#[derive(CondKeys)]` of cwa_images
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Token};
+
+/// required keys are the field names, `#[serde(rename = "...")]` honored.
+/// fields with `#[condkeys(skip)]`, `#[serde(skip)]` or `#[serde(flatten)]`
+/// are not required
+#[proc_macro_derive(CondKeys, attributes(condkeys))]
+pub fn derive_cond_keys(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "CondKeys is for structs only",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "CondKeys needs named fields",
+        ));
+    };
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                return Err(meta.error("rename_all is not supported by CondKeys"));
+            }
+            skip_value(&meta)
+        })?;
+    }
+
+    let mut keys = Vec::new();
+    for field in &fields.named {
+        let mut key = field.ident.as_ref().unwrap().to_string();
+        let mut skip = false;
+        for attr in &field.attrs {
+            if attr.path().is_ident("condkeys") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        skip = true;
+                        return Ok(());
+                    }
+                    Err(meta.error("expect #[condkeys(skip)]"))
+                })?;
+            } else if attr.path().is_ident("serde") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        if meta.input.peek(Token![=]) {
+                            key = meta.value()?.parse::<LitStr>()?.value();
+                        } else {
+                            // rename(deserialize = "...")
+                            meta.parse_nested_meta(|meta| {
+                                if meta.path.is_ident("deserialize") {
+                                    key = meta.value()?.parse::<LitStr>()?.value();
+                                    return Ok(());
+                                }
+                                skip_value(&meta)
+                            })?;
+                        }
+                        return Ok(());
+                    }
+                    if ["skip", "skip_deserializing", "flatten"]
+                        .iter()
+                        .any(|x| meta.path.is_ident(x))
+                    {
+                        skip = true;
+                    }
+                    skip_value(&meta)
+                })?;
+            }
+        }
+        if !skip {
+            keys.push(key);
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::cwa_images::parser::CondKeys for #name #ty_generics #where_clause {
+            fn keys<'a>() -> &'a [&'a str] {
+                &[#(#keys),*]
+            }
+        }
+    })
+}
+
+/// other serde attributes, e.g. `default = "..."` or `with = "..."`
+fn skip_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<proc_macro2::TokenStream>()?;
+    }
+    Ok(())
+}
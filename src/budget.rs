@@ -0,0 +1,106 @@
+//! byte budget of a cycle, shared by all tasks
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default)]
+pub struct Budget {
+    limit: Option<u64>,
+    used: AtomicU64,
+}
+
+impl Budget {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// bytes saved since last reset
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, bytes: u64) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// no new download should be started. the last one may go over the limit
+    pub fn is_exhausted(&self) -> bool {
+        self.limit.is_some_and(|x| self.used() >= x)
+    }
+
+    /// start of a cycle
+    pub fn reset(&self) {
+        self.used.store(0, Ordering::Relaxed);
+    }
+}
+
+/// `--max-bytes`, binary units. e.g. 500M, 2G, 1.5GB or 1048576
+pub fn parse_bytes(text: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size {:?}, expect e.g. 500M or 2G", text);
+    let text = text.trim();
+    let text = text
+        .strip_suffix("iB")
+        .or_else(|| text.strip_suffix('B'))
+        .unwrap_or(text);
+    let (number, scale) = match text.char_indices().last() {
+        Some((i, unit)) if unit.is_ascii_alphabetic() => {
+            let power = match unit.to_ascii_uppercase() {
+                'K' => 1,
+                'M' => 2,
+                'G' => 3,
+                'T' => 4,
+                _ => return Err(invalid()),
+            };
+            (&text[..i], 1024u64.pow(power))
+        }
+        _ => (text, 1),
+    };
+
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bytes = number * scale as f64;
+    if !bytes.is_finite() || bytes < 1.0 || bytes > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(Ok(1048576), parse_bytes("1048576"));
+        assert_eq!(Ok(500 * 1024 * 1024), parse_bytes("500M"));
+        assert_eq!(Ok(2 * 1024 * 1024 * 1024), parse_bytes("2G"));
+        assert_eq!(Ok(3 * 1024 * 1024 * 1024 / 2), parse_bytes("1.5GB"));
+        assert_eq!(Ok(512 * 1024), parse_bytes("512KiB"));
+        assert_eq!(Ok(100), parse_bytes("100B"));
+
+        assert!(parse_bytes("").is_err());
+        assert!(parse_bytes("0").is_err());
+        assert!(parse_bytes("-1M").is_err());
+        assert!(parse_bytes("5X").is_err());
+        assert!(parse_bytes("M").is_err());
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget::new(Some(100));
+        assert!(!budget.is_exhausted());
+        budget.add(60);
+        assert!(!budget.is_exhausted());
+        budget.add(60);
+        assert!(budget.is_exhausted());
+        assert_eq!(120, budget.used());
+
+        budget.reset();
+        assert!(!budget.is_exhausted());
+        assert!(!Budget::default().is_exhausted());
+    }
+}
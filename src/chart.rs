@@ -0,0 +1,72 @@
+//! weather chart (analysis and forecast map) list entries
+use crate::parser::CondKeys;
+use crate::task::Img;
+use crate::timestamp;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// chart entry, file names have no time but the entry does
+#[derive(Debug, Deserialize, CondKeys)]
+pub struct Chart {
+    pub img: String,
+    pub text: String,
+    /// e.g. `SFC` surface analysis, `FCST` forecast
+    #[serde(default, rename = "type")]
+    #[condkeys(skip)]
+    pub chart_type: Option<String>,
+    /// e.g. `2024-07-24 08:00`
+    #[serde(default, rename = "validTime")]
+    #[condkeys(skip)]
+    pub valid_time: Option<String>,
+}
+
+impl From<Chart> for Img {
+    fn from(chart: Chart) -> Self {
+        let mut extra = Map::new();
+        if let Some(chart_type) = chart.chart_type {
+            extra.insert("chart_type".into(), Value::String(chart_type));
+        }
+        let time = chart.valid_time.as_deref().and_then(timestamp::parse_time);
+        if let Some(valid_time) = chart.valid_time {
+            extra.insert("valid_time".into(), Value::String(valid_time));
+        }
+
+        Img {
+            img: chart.img,
+            text: chart.text,
+            time,
+            extra,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Chart;
+    use crate::parser::{find_objects, parse_source};
+    use crate::task::Img;
+
+    pub const FIXTURE: &str = include_str!("../testdata/Weather_chart.js");
+
+    #[test]
+    fn test_parse_charts() {
+        let charts: Vec<Chart> = find_objects(parse_source(FIXTURE).unwrap());
+        assert_eq!(6, charts.len());
+        // keys of the list object are sorted
+        assert_eq!(Some("FCST"), charts[0].chart_type.as_deref());
+
+        let imgs: Vec<Img> = charts.into_iter().map(Img::from).collect();
+        assert_eq!("SFC_ANALYSIS_01.jpg", imgs[2].img);
+        assert_eq!("地面天氣圖 07/24 08:00", imgs[2].text);
+        assert_eq!(
+            "2024-07-24 08:00",
+            imgs[2].time().unwrap().format("%Y-%m-%d %H:%M").to_string()
+        );
+        assert_eq!("SFC", imgs[2].extra["chart_type"]);
+        assert_eq!("2024-07-24 08:00", imgs[2].extra["valid_time"]);
+
+        // no valid time
+        assert_eq!(None, imgs[5].time());
+        assert!(!imgs[5].extra.contains_key("valid_time"));
+    }
+}
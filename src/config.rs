@@ -0,0 +1,351 @@
+//! task config file
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default, rename = "task")]
+    pub tasks: Vec<TaskConfig>,
+    #[serde(default, rename = "template")]
+    pub templates: BTreeMap<String, Template>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TaskConfig {
+    /// for logs and metrics
+    pub name: Option<String>,
+    /// path of images list url, relative to --host
+    pub list: String,
+    /// path of images dir, relative to --host
+    pub dir: String,
+    /// every file if none, e.g. to filter by `text_contains` only
+    pub contains: Option<Patterns>,
+    /// override global exclude patterns
+    pub exclude: Option<Patterns>,
+    /// override `--contains-any`
+    pub contains_any: Option<Patterns>,
+    /// override `--contains-all`
+    pub contains_all: Option<Patterns>,
+    /// override global text filter, substrings of the description
+    pub text_contains: Option<Patterns>,
+    /// seconds or e.g. `"10m"`
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub interval: Option<Duration>,
+    /// local download dir instead of the global one
+    pub out_dir: Option<String>,
+}
+
+/// task expanded for each instance, `${variable}` is replaced by the instance
+#[derive(Debug, Deserialize)]
+pub struct Template {
+    #[serde(default = "default_variable")]
+    pub variable: String,
+    pub instances: Vec<String>,
+    #[serde(flatten)]
+    pub task: TaskConfig,
+}
+
+fn default_variable() -> String {
+    "instance".into()
+}
+
+/// a single pattern or a list of patterns
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<Patterns> for Vec<String> {
+    fn from(value: Patterns) -> Self {
+        match value {
+            Patterns::One(pattern) => vec![pattern],
+            Patterns::Many(patterns) => patterns,
+        }
+    }
+}
+
+impl Patterns {
+    fn map<F>(&self, f: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Result<String, String>,
+    {
+        match self {
+            Self::One(pattern) => Ok(Self::One(f(pattern)?)),
+            Self::Many(patterns) => Ok(Self::Many(
+                patterns.iter().map(|x| f(x)).collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+impl Template {
+    /// concrete tasks of each instance
+    pub fn expand(&self, name: &str) -> Result<Vec<TaskConfig>, String> {
+        if self.instances.is_empty() {
+            return Err(format!("template {} has no instances", name));
+        }
+
+        let mut tasks = Vec::new();
+        for instance in &self.instances {
+            let sub = |x: &str| substitute(x, &self.variable, instance, name);
+            let sub_patterns = |x: &Option<Patterns>| x.as_ref().map(|x| x.map(sub)).transpose();
+            let task_name = match &self.task.name {
+                Some(task_name) => sub(task_name)?,
+                None => format!("{}_{}", name, instance),
+            };
+
+            tasks.push(TaskConfig {
+                name: Some(task_name),
+                list: sub(&self.task.list)?,
+                dir: sub(&self.task.dir)?,
+                contains: sub_patterns(&self.task.contains)?,
+                exclude: sub_patterns(&self.task.exclude)?,
+                contains_any: sub_patterns(&self.task.contains_any)?,
+                contains_all: sub_patterns(&self.task.contains_all)?,
+                text_contains: sub_patterns(&self.task.text_contains)?,
+                interval: self.task.interval,
+                out_dir: self.task.out_dir.as_deref().map(sub).transpose()?,
+            });
+        }
+        Ok(tasks)
+    }
+}
+
+/// replace `${variable}` with `value`, other placeholders are error
+fn substitute(text: &str, variable: &str, value: &str, template: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!(
+                "unclosed placeholder in template {}: {}",
+                template, text
+            ));
+        };
+        let placeholder = &rest[start + 2..start + end];
+        if placeholder != variable {
+            return Err(format!(
+                "unknown placeholder ${{{}}} in template {}",
+                placeholder, template
+            ));
+        }
+        out.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+impl Config {
+    /// tasks with templates expanded
+    pub fn into_tasks(self) -> Result<Vec<TaskConfig>, String> {
+        let mut tasks = self.tasks;
+        for (name, template) in &self.templates {
+            tasks.extend(template.expand(name)?);
+        }
+        Ok(tasks)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&source)?)
+    }
+}
+
+/// `--tasks`, a json array of tasks. errors name the index of the entry
+pub fn parse_tasks(source: &str) -> Result<Vec<TaskConfig>, String> {
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(source).map_err(|err| format!("expect array of tasks: {}", err))?;
+
+    let mut tasks = Vec::new();
+    for (i, value) in values.into_iter().enumerate() {
+        let task: TaskConfig =
+            serde_json::from_value(value).map_err(|err| format!("invalid task {}: {}", i, err))?;
+        if task.list.is_empty() || task.dir.is_empty() {
+            return Err(format!("invalid task {}: list and dir cannot be empty", i));
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+/// `--tasks` file, `-` for stdin
+pub fn load_tasks(path: &str) -> Result<Vec<TaskConfig>, Box<dyn Error>> {
+    let source = if path == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(parse_tasks(&source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use super::{parse_tasks, substitute, Config, Patterns, TaskConfig};
+
+    const SAMPLE: &str = r#"
+[[task]]
+list = "/Data/js/obs_img/Observe_sat.js"
+dir = "/Data/satellite/"
+contains = "LCC_TRGB"
+
+[[task]]
+name = "rain"
+list = "/Data/js/obs_img/Observe_radar_rain.js"
+dir = "/Data/radar_rain/"
+contains = ["RCLY_3600", "RCLY_0900"]
+exclude = []
+interval = 600
+out_dir = "/srv/fast/radar_rain"
+
+[[task]]
+list = "/Data/js/obs_img/Observe_sat.js"
+dir = "/Data/satellite/"
+text_contains = "彩色"
+"#;
+
+    #[test]
+    fn test_load() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(SAMPLE.as_bytes()).unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(
+            vec![
+                TaskConfig {
+                    name: None,
+                    list: "/Data/js/obs_img/Observe_sat.js".into(),
+                    dir: "/Data/satellite/".into(),
+                    contains: Some(Patterns::One("LCC_TRGB".into())),
+                    exclude: None,
+                    contains_any: None,
+                    contains_all: None,
+                    text_contains: None,
+                    interval: None,
+                    out_dir: None,
+                },
+                TaskConfig {
+                    name: Some("rain".into()),
+                    list: "/Data/js/obs_img/Observe_radar_rain.js".into(),
+                    dir: "/Data/radar_rain/".into(),
+                    contains: Some(Patterns::Many(vec!["RCLY_3600".into(), "RCLY_0900".into()])),
+                    exclude: Some(Patterns::Many(Vec::new())),
+                    contains_any: None,
+                    contains_all: None,
+                    text_contains: None,
+                    interval: Some(Duration::from_secs(600)),
+                    out_dir: Some("/srv/fast/radar_rain".into()),
+                },
+                // text only, no file name pattern
+                TaskConfig {
+                    name: None,
+                    list: "/Data/js/obs_img/Observe_sat.js".into(),
+                    dir: "/Data/satellite/".into(),
+                    contains: None,
+                    exclude: None,
+                    contains_any: None,
+                    contains_all: None,
+                    text_contains: Some(Patterns::One("彩色".into())),
+                    interval: None,
+                    out_dir: None,
+                }
+            ],
+            config.tasks
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Config::parse("").unwrap().tasks.is_empty());
+        assert!(Config::parse("[[task]]\nlist = \"/a.js\"").is_err());
+        assert!(Config::parse("[[task]]\nlist = 1\ndir = \"/\"\ncontains = \"a\"").is_err());
+        let zero = "[[task]]\nlist = \"/a.js\"\ndir = \"/\"\ncontains = \"a\"\ninterval = \"0m\"";
+        assert!(Config::parse(zero).is_err());
+    }
+
+    const TEMPLATE: &str = r#"
+[template.radar_station]
+variable = "station"
+instances = ["RCWF", "RCHL"]
+list = "/Data/js/obs_img/Observe_radar_${station}.js"
+dir = "/Data/radar/"
+contains = ["CV1_${station}", "CV2_${station}"]
+interval = "5m"
+"#;
+
+    #[test]
+    fn test_template() {
+        let tasks = Config::parse(TEMPLATE).unwrap().into_tasks().unwrap();
+        assert_eq!(2, tasks.len());
+        assert_eq!(Some("radar_station_RCWF".into()), tasks[0].name);
+        assert_eq!("/Data/js/obs_img/Observe_radar_RCHL.js", tasks[1].list);
+        assert_eq!("/Data/radar/", tasks[1].dir);
+        assert_eq!(
+            Some(Patterns::Many(vec!["CV1_RCHL".into(), "CV2_RCHL".into()])),
+            tasks[1].contains
+        );
+        assert_eq!(Some(Duration::from_secs(300)), tasks[1].interval);
+
+        let named = "[template.sat]\ninstances = [\"B13\"]\nname = \"sat-${instance}\"\nlist = \"/a.js\"\ndir = \"/\"\ncontains = \"${instance}\"";
+        let tasks = Config::parse(named).unwrap().into_tasks().unwrap();
+        assert_eq!(Some("sat-B13".into()), tasks[0].name);
+        assert_eq!(Some(Patterns::One("B13".into())), tasks[0].contains);
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let unknown = TEMPLATE.replace("CV2_${station}", "CV2_${stn}");
+        let err = Config::parse(&unknown).unwrap().into_tasks().unwrap_err();
+        assert_eq!("unknown placeholder ${stn} in template radar_station", err);
+
+        let empty = TEMPLATE.replace(r#"["RCWF", "RCHL"]"#, "[]");
+        let err = Config::parse(&empty).unwrap().into_tasks().unwrap_err();
+        assert_eq!("template radar_station has no instances", err);
+    }
+
+    #[test]
+    fn test_parse_tasks() {
+        let json = r#"[
+            {"list": "/Data/js/obs_img/Observe_sat.js", "dir": "/Data/satellite/", "contains": "LCC_TRGB"},
+            {"name": "rain", "list": "/Data/js/obs_img/Observe_radar_rain.js", "dir": "/Data/radar_rain/", "contains": ["RCLY_3600"], "interval": "10m"}
+        ]"#;
+        let tasks = parse_tasks(json).unwrap();
+        assert_eq!(2, tasks.len());
+        assert_eq!(Some(Patterns::One("LCC_TRGB".into())), tasks[0].contains);
+        assert_eq!(Some("rain".into()), tasks[1].name);
+        assert_eq!(Some(Duration::from_secs(600)), tasks[1].interval);
+        assert!(parse_tasks("[]").unwrap().is_empty());
+
+        let err = parse_tasks(r#"[{"list": "/a.js", "dir": "/"}, {"list": "/b.js"}]"#).unwrap_err();
+        assert!(
+            err.starts_with("invalid task 1: missing field `dir`"),
+            "{}",
+            err
+        );
+        let err = parse_tasks(r#"[{"list": "", "dir": "/"}]"#).unwrap_err();
+        assert_eq!("invalid task 0: list and dir cannot be empty", err);
+        assert!(parse_tasks(r#"{"list": "/a.js", "dir": "/"}"#).is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!("a_B13_b", substitute("a_${x}_b", "x", "B13", "t").unwrap());
+        assert_eq!("B13B13", substitute("${x}${x}", "x", "B13", "t").unwrap());
+        assert_eq!("$x {x}", substitute("$x {x}", "x", "B13", "t").unwrap());
+        assert!(substitute("a_${x", "x", "B13", "t").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! `--dedupe`, an image saved again under another name is hard linked to the
+//! first copy, e.g. reissued with a new time in file name
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs::{hard_link, remove_file, rename};
+
+pub const FILE_NAME: &str = "dedupe.json";
+
+/// first saved file of each blake3 hash, kept across runs in `file`
+#[derive(Debug)]
+pub struct Dedupe {
+    index: Mutex<HashMap<String, PathBuf>>,
+    file: PathBuf,
+}
+
+impl Dedupe {
+    /// empty index written to `file`, see `load`
+    pub fn new(file: PathBuf) -> Self {
+        Self {
+            index: Mutex::new(HashMap::new()),
+            file,
+        }
+    }
+
+    /// index written by a previous run, none if missing
+    pub fn load(&self) -> io::Result<()> {
+        let index = match std::fs::read(&self.file) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        *self.index.lock().unwrap() = index;
+        Ok(())
+    }
+
+    /// replace the file by rename, a crash never leaves a partial one
+    pub fn flush(&self) -> io::Result<()> {
+        let data = serde_json::to_vec(&*self.index.lock().unwrap())?;
+        let mut tmp = OsString::from(self.file.as_os_str());
+        tmp.push(".tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &self.file)
+    }
+
+    /// replace the just saved `path` by a hard link to an earlier file of
+    /// the same content, returns that file. the first one of its content is
+    /// indexed instead
+    pub async fn link(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        let hash = blake3::hash(&tokio::fs::read(path).await?)
+            .to_hex()
+            .to_string();
+        let original = {
+            let mut index = self.index.lock().unwrap();
+            match index.get(&hash) {
+                // removed since, e.g. by retention
+                Some(original) if original != path && original.is_file() => original.clone(),
+                Some(original) if original == path => return Ok(None),
+                _ => {
+                    index.insert(hash, path.to_path_buf());
+                    return Ok(None);
+                }
+            }
+        };
+
+        // replace in one rename, `path` is never missing
+        let mut tmp = OsString::from(path.as_os_str());
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let _ = remove_file(&tmp).await;
+        hard_link(&original, &tmp).await?;
+        rename(&tmp, path).await?;
+        Ok(Some(original))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("CV1_3600_202407241200.png");
+        let reissued = dir.path().join("CV1_3600_202407241210.png");
+        let other = dir.path().join("CV1_3600_202407241220.png");
+        std::fs::write(&first, "png").unwrap();
+        std::fs::write(&reissued, "png").unwrap();
+        std::fs::write(&other, "gif").unwrap();
+
+        let dedupe = Dedupe::new(dir.path().join(FILE_NAME));
+        assert_eq!(None, dedupe.link(&first).await.unwrap());
+        assert_eq!(Some(first.clone()), dedupe.link(&reissued).await.unwrap());
+        assert_eq!(None, dedupe.link(&other).await.unwrap());
+        // saved again under the same name
+        assert_eq!(None, dedupe.link(&first).await.unwrap());
+        assert_eq!("png", std::fs::read_to_string(&reissued).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |x: &Path| std::fs::metadata(x).unwrap().ino();
+            assert_eq!(inode(&first), inode(&reissued));
+            assert_ne!(inode(&first), inode(&other));
+        }
+
+        // indexed across runs
+        dedupe.flush().unwrap();
+        let copy = dir.path().join("CV1_3600_202407241230.png");
+        std::fs::write(&copy, "gif").unwrap();
+        let restarted = Dedupe::new(dir.path().join(FILE_NAME));
+        restarted.load().unwrap();
+        assert_eq!(Some(other.clone()), restarted.link(&copy).await.unwrap());
+
+        // first copy removed, e.g. by retention
+        std::fs::remove_file(&first).unwrap();
+        assert_eq!(None, restarted.link(&reissued).await.unwrap());
+        assert_eq!(None, restarted.link(&reissued).await.unwrap());
+    }
+}
@@ -0,0 +1,62 @@
+//! durations of intervals, e.g. `600`, `10m` or `1h30m`
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+
+/// shorter intervals are allowed, with a warning
+pub const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// bare number is seconds, `0` is one-shot
+pub fn parse(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let duration = humantime::parse_duration(text)
+        .map_err(|err| format!("{}, expect seconds or e.g. 30s, 10m, 1h30m", err))?;
+    if duration.is_zero() {
+        return Err(format!("{:?} is zero, use 0 to run once", text));
+    }
+    Ok(duration)
+}
+
+/// `600` or `"10m"` in config files
+pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Option::<Value>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Value::Seconds(secs)) => Ok(Some(Duration::from_secs(secs))),
+        Some(Value::Text(text)) => parse(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Duration::from_secs(600), parse("600").unwrap());
+        assert_eq!(Duration::ZERO, parse("0").unwrap());
+        assert_eq!(Duration::from_secs(30), parse("30s").unwrap());
+        assert_eq!(Duration::from_secs(600), parse("10m").unwrap());
+        assert_eq!(Duration::from_secs(5400), parse("1h30m").unwrap());
+        assert_eq!(Duration::from_secs(5400), parse("1h 30m").unwrap());
+
+        assert!(parse("0s").unwrap_err().contains("use 0"));
+        assert!(parse("0m").is_err());
+        assert!(parse("10 minutes later").is_err());
+        assert!(parse("-10").is_err());
+        assert!(parse("").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! names of downloaded files, read once per dir each cycle instead of a stat
+//! per image
+use log::{debug, info};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub const DEFAULT_SCAN_LIMIT: usize = 100_000;
+
+pub trait Fs: Debug + Send + Sync {
+    /// names of files in `dir`, `None` if it has more than `limit` entries
+    fn list_files(&self, dir: &Path, limit: usize) -> io::Result<Option<HashSet<String>>>;
+    fn is_file(&self, path: &Path) -> bool;
+}
+
+#[derive(Debug)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn list_files(&self, dir: &Path, limit: usize) -> io::Result<Option<HashSet<String>>> {
+        let mut names = HashSet::new();
+        for (i, entry) in std::fs::read_dir(dir)?.enumerate() {
+            if i >= limit {
+                return Ok(None);
+            }
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.insert(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(Some(names))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+#[derive(Debug)]
+pub struct Existing {
+    fs: Box<dyn Fs>,
+    /// max entries of a dir kept in memory, 0 is always stat
+    limit: usize,
+    /// `None` if the dir is too large, files are checked one by one
+    dirs: Mutex<HashMap<PathBuf, Option<HashSet<String>>>>,
+}
+
+impl Default for Existing {
+    fn default() -> Self {
+        Self::new(Box::new(RealFs), DEFAULT_SCAN_LIMIT)
+    }
+}
+
+impl Existing {
+    pub fn new(fs: Box<dyn Fs>, limit: usize) -> Self {
+        Self {
+            fs,
+            limit,
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// forget scanned dirs, on start of each cycle
+    pub fn clear(&self) {
+        self.dirs.lock().unwrap().clear();
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return self.fs.is_file(path);
+        };
+        if self.limit == 0 {
+            return self.fs.is_file(path);
+        }
+
+        let mut dirs = self.dirs.lock().unwrap();
+        let names = dirs
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| self.scan(dir));
+        match names {
+            Some(names) => names.contains(name.to_string_lossy().as_ref()),
+            None => self.fs.is_file(path),
+        }
+    }
+
+    /// a new file is written
+    pub fn insert(&self, path: &Path) {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+        if let Some(Some(names)) = self.dirs.lock().unwrap().get_mut(dir) {
+            names.insert(name.to_string_lossy().into_owned());
+        }
+    }
+
+    fn scan(&self, dir: &Path) -> Option<HashSet<String>> {
+        let start = Instant::now();
+        match self.fs.list_files(dir, self.limit) {
+            Ok(Some(names)) => {
+                debug!(
+                    "scanned {} files of {} in {:?}",
+                    names.len(),
+                    dir.to_string_lossy(),
+                    start.elapsed()
+                );
+                Some(names)
+            }
+            Ok(None) => {
+                info!(
+                    "{} has more than {} entries, check files one by one",
+                    dir.to_string_lossy(),
+                    self.limit
+                );
+                None
+            }
+            // not created yet
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Some(HashSet::new()),
+            Err(err) => {
+                debug!("cannot scan {} {}", dir.to_string_lossy(), err);
+                None
+            }
+        }
+    }
+}
+
+/// `--force`, files saved before are downloaded again, once per process
+#[derive(Debug, Default)]
+pub struct Force(Mutex<HashSet<PathBuf>>);
+
+impl Force {
+    /// not overwritten yet
+    pub fn is_pending(&self, path: &Path) -> bool {
+        !self.0.lock().unwrap().contains(path)
+    }
+
+    pub fn done(&self, path: &Path) {
+        self.0.lock().unwrap().insert(path.to_path_buf());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Existing, Fs, RealFs};
+    use std::collections::HashSet;
+    use std::io;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct Calls {
+        list_files: AtomicUsize,
+        is_file: AtomicUsize,
+    }
+
+    /// real fs counting calls
+    #[derive(Debug)]
+    struct CountingFs(Arc<Calls>);
+
+    impl Fs for CountingFs {
+        fn list_files(&self, dir: &Path, limit: usize) -> io::Result<Option<HashSet<String>>> {
+            self.0.list_files.fetch_add(1, Ordering::SeqCst);
+            RealFs.list_files(dir, limit)
+        }
+
+        fn is_file(&self, path: &Path) -> bool {
+            self.0.is_file.fetch_add(1, Ordering::SeqCst);
+            RealFs.is_file(path)
+        }
+    }
+
+    fn setup(count: usize) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..count {
+            std::fs::write(dir.path().join(format!("CV1_3600_{:012}.png", i)), "").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("subdir")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_single_scan() {
+        let dir = setup(10_000);
+        let calls = Arc::new(Calls::default());
+        let existing = Existing::new(Box::new(CountingFs(calls.clone())), 100_000);
+
+        for i in 0..10_010 {
+            let path = dir.path().join(format!("CV1_3600_{:012}.png", i));
+            assert_eq!(i < 10_000, existing.is_file(&path));
+        }
+        assert!(!existing.is_file(&dir.path().join("subdir")));
+        assert_eq!(1, calls.list_files.load(Ordering::SeqCst));
+        assert_eq!(0, calls.is_file.load(Ordering::SeqCst));
+
+        // written in this cycle
+        let new = dir.path().join("CV1_3600_new.png");
+        std::fs::write(&new, "").unwrap();
+        existing.insert(&new);
+        assert!(existing.is_file(&new));
+        assert_eq!(1, calls.list_files.load(Ordering::SeqCst));
+
+        existing.clear();
+        assert!(existing.is_file(&new));
+        assert_eq!(2, calls.list_files.load(Ordering::SeqCst));
+
+        // dir not created yet
+        assert!(!existing.is_file(&dir.path().join("radar").join("a.png")));
+        assert_eq!(0, calls.is_file.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_over_limit() {
+        let dir = setup(100);
+        let calls = Arc::new(Calls::default());
+        let existing = Existing::new(Box::new(CountingFs(calls.clone())), 50);
+
+        for i in 0..110 {
+            let path = dir.path().join(format!("CV1_3600_{:012}.png", i));
+            assert_eq!(i < 100, existing.is_file(&path));
+        }
+        assert_eq!(1, calls.list_files.load(Ordering::SeqCst));
+        assert_eq!(110, calls.is_file.load(Ordering::SeqCst));
+    }
+}
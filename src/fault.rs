@@ -0,0 +1,313 @@
+//! fault injection for chaos testing, only built with the `faults` feature.
+//! configured by `CWA_FAULTS` env, e.g.
+//! `fail=0.1,status=503,truncate=0.2,truncate_at=1024,delay_ms=50,rename=0.1,seed=42`,
+//! or per task with [`scope`]
+#[cfg(not(debug_assertions))]
+compile_error!("faults feature is for testing, not for release builds");
+
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use log::warn;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Body, Response};
+use std::env;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Faults {
+    /// probability of a request answered with `status`
+    pub fail: f64,
+    pub status: u16,
+    /// probability of a body cut with connection reset
+    pub truncate: f64,
+    /// bytes of body sent before cut
+    pub truncate_at: usize,
+    /// added to every request
+    pub delay: Duration,
+    /// probability of a rename failing
+    pub rename: f64,
+    pub seed: u64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self {
+            fail: 0.0,
+            status: 503,
+            truncate: 0.0,
+            truncate_at: 0,
+            delay: Duration::ZERO,
+            rename: 0.0,
+            seed: 1,
+        }
+    }
+}
+
+impl Faults {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut faults = Self::default();
+        for pair in text.split(',').filter(|x| !x.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or(format!("expect key=value, got {:?}", pair))?;
+            let invalid = || format!("invalid value of {}: {:?}", key, value);
+            match key.trim() {
+                "fail" => faults.fail = value.parse().map_err(|_| invalid())?,
+                "status" => faults.status = value.parse().map_err(|_| invalid())?,
+                "truncate" => faults.truncate = value.parse().map_err(|_| invalid())?,
+                "truncate_at" => faults.truncate_at = value.parse().map_err(|_| invalid())?,
+                "delay_ms" => {
+                    faults.delay = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+                }
+                "rename" => faults.rename = value.parse().map_err(|_| invalid())?,
+                "seed" => faults.seed = value.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("unknown fault {:?}", key)),
+            }
+        }
+        Ok(faults)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    faults: Faults,
+    rng: u64,
+}
+
+impl State {
+    fn new(faults: Faults) -> Self {
+        let rng = faults.seed.max(1);
+        Self { faults, rng }
+    }
+
+    /// true by `rate`, xorshift64*
+    fn roll(&mut self, rate: f64) -> bool {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let value = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        (value as f64 / (1u64 << 53) as f64) < rate
+    }
+}
+
+lazy_static! {
+    static ref GLOBAL: Mutex<Option<State>> = Mutex::new(match env::var("CWA_FAULTS") {
+        Ok(text) => match Faults::parse(&text) {
+            Ok(faults) => {
+                warn!("fault injection enabled: {:?}", faults);
+                Some(State::new(faults))
+            }
+            Err(err) => panic!("invalid CWA_FAULTS {}", err),
+        },
+        Err(_) => None,
+    });
+}
+
+tokio::task_local! {
+    static SCOPED: Mutex<State>;
+}
+
+/// run `f` with `faults` instead of the env ones, e.g. in tests running in
+/// parallel
+#[cfg(test)]
+pub async fn scope<F: std::future::Future>(faults: Faults, f: F) -> F::Output {
+    SCOPED.scope(Mutex::new(State::new(faults)), f).await
+}
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> Option<R> {
+    if SCOPED.try_with(|_| ()).is_ok() {
+        Some(SCOPED.with(|x| f(&mut x.lock().unwrap())))
+    } else {
+        GLOBAL.lock().unwrap().as_mut().map(f)
+    }
+}
+
+/// request seam: delay, replace by error status or cut the body
+pub async fn response(resp: Response) -> Result<Response, reqwest::Error> {
+    let Some((delay, fail, truncate)) = with_state(|x| {
+        let fail = x.roll(x.faults.fail).then_some(x.faults.status);
+        let truncate = x.roll(x.faults.truncate).then_some(x.faults.truncate_at);
+        (x.faults.delay, fail, truncate)
+    }) else {
+        return Ok(resp);
+    };
+    tokio::time::sleep(delay).await;
+
+    let mut builder = http::Response::builder().status(resp.status());
+    *builder.headers_mut().unwrap() = resp.headers().clone();
+    if let Some(status) = fail {
+        let builder = http::Response::builder().status(status);
+        return Ok(Response::from(
+            builder.body(Body::from("injected")).unwrap(),
+        ));
+    }
+
+    let Some(at) = truncate else {
+        return Ok(resp);
+    };
+    let body = resp.bytes().await?;
+    if at >= body.len() {
+        return Ok(Response::from(builder.body(Body::from(body)).unwrap()));
+    }
+    let chunks: Vec<Result<Bytes, io::Error>> = vec![
+        Ok(body.slice(..at)),
+        Err(io::Error::new(io::ErrorKind::ConnectionReset, "injected")),
+    ];
+    builder.headers_mut().unwrap().remove(CONTENT_LENGTH);
+    let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+    Ok(Response::from(builder.body(body).unwrap()))
+}
+
+/// storage seam: fail before rename
+pub fn rename() -> io::Result<()> {
+    match with_state(|x| x.roll(x.faults.rename)) {
+        Some(true) => Err(io::Error::other("injected rename failure")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scope, Faults};
+    use crate::manifest::{self, Manifest};
+    use crate::matcher::Matcher;
+    use crate::metrics::Report;
+    use crate::mock::{self, Response};
+    use crate::request::CwaClient;
+    use crate::shutdown::Shutdown;
+    use crate::task::{RunOptions, Task};
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse() {
+        let faults = Faults::parse("fail=0.1,status=500,truncate_at=1024,delay_ms=5").unwrap();
+        assert_eq!(0.1, faults.fail);
+        assert_eq!(500, faults.status);
+        assert_eq!(1024, faults.truncate_at);
+        assert_eq!(Duration::from_millis(5), faults.delay);
+        assert!(Faults::parse("fail").is_err());
+        assert!(Faults::parse("crash=1").is_err());
+    }
+
+    const FRAMES: usize = 20;
+
+    fn body(path: &str) -> Vec<u8> {
+        path.as_bytes().iter().cycle().take(4096).copied().collect()
+    }
+
+    /// runs cycles with faults, the second stopped by shutdown, then a clean
+    /// one
+    #[tokio::test]
+    async fn test_soak() {
+        let addr = mock::serve(|req| {
+            if req.path.ends_with(".js") {
+                let entries: Vec<String> = (0..FRAMES)
+                    .map(|i| {
+                        format!(
+                            r#"{{"img": "CV1_3600_2024072412{:02}.png", "text": ""}}"#,
+                            i
+                        )
+                    })
+                    .collect();
+                Response::new(200, format!("var data = [{}];", entries.join(",")))
+            } else {
+                Response::new(200, body(&req.path))
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut opts = RunOptions {
+            manifest: Some(Manifest::default()),
+            ..Default::default()
+        };
+        let client = CwaClient::default();
+        let task = Task::new(
+            "radar".to_string(),
+            format!("http://{}/Observe_radar.js", addr),
+            format!("http://{}/Data/radar/", addr),
+            Matcher::Contains(vec!["CV1_".to_string()]),
+        );
+        let faults = Faults {
+            fail: 0.2,
+            truncate: 0.3,
+            truncate_at: 1000,
+            delay: Duration::from_millis(1),
+            rename: 0.2,
+            seed: 7,
+            ..Default::default()
+        };
+
+        let mut saved = 0;
+        let mut failed = 0;
+        for cycle in 0..7 {
+            if cycle == 1 {
+                let shutdown = opts.shutdown.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(3)).await;
+                    shutdown.request();
+                });
+            } else if cycle == 2 {
+                opts.shutdown = Shutdown::default();
+            }
+            let run = task.run(&client, dir.path(), &opts, None);
+            let result = if cycle < 6 {
+                scope(faults.clone(), run).await
+            } else {
+                run.await
+            };
+            // stopped cycle ends without error, in-flight file is dropped
+            assert!(cycle != 1 || result.is_ok());
+            opts.manifest.as_ref().unwrap().flush().unwrap();
+
+            let before = saved;
+            match result {
+                Ok(Report {
+                    saved: count,
+                    failures,
+                    ..
+                }) => {
+                    saved += count as usize;
+                    failed += failures.values().sum::<u64>();
+                }
+                // list failed
+                Err(_) => failed += 1,
+            }
+
+            // no partial or temp files, counts add up
+            let mut files = BTreeSet::new();
+            for entry in std::fs::read_dir(dir.path()).unwrap() {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                assert!(
+                    !name.contains(".part") && !name.ends_with(".tmp"),
+                    "left {}",
+                    name
+                );
+                if name == manifest::FILE_NAME {
+                    continue;
+                }
+                assert_eq!(
+                    body(&format!("/Data/radar/{}", name)),
+                    std::fs::read(&path).unwrap()
+                );
+                files.insert(name);
+            }
+            assert_eq!(saved, files.len());
+            assert!(saved >= before);
+
+            // manifest covers exactly the files on disk
+            let recorded: BTreeSet<String> = manifest::read(dir.path())
+                .unwrap()
+                .into_iter()
+                .map(|x| x.file)
+                .collect();
+            assert_eq!(files, recorded);
+            assert!(manifest::verify(dir.path()).unwrap().is_empty());
+        }
+        assert!(failed > 0, "no fault injected");
+        assert_eq!(FRAMES, saved);
+    }
+}
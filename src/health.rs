@@ -0,0 +1,146 @@
+//! `/healthz` endpoint for orchestration probes
+use crate::metrics::Report;
+use log::{debug, info};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// run state shared with the endpoint
+#[derive(Debug, Clone)]
+pub struct Health(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    window: Duration,
+    last_ok: Mutex<Option<Instant>>,
+}
+
+/// task run counts as fresh if something was saved, or nothing was new
+pub fn is_fresh(report: Option<&Report>) -> bool {
+    report.is_some_and(|x| x.saved > 0 || x.failures.is_empty())
+}
+
+impl Health {
+    pub fn new(window: Duration) -> Self {
+        Self(Arc::new(Inner {
+            window,
+            last_ok: Mutex::new(None),
+        }))
+    }
+
+    /// record a finished run
+    pub fn record(&self, ok: bool, now: Instant) {
+        if ok {
+            *self.0.last_ok.lock().unwrap() = Some(now);
+        }
+    }
+
+    pub fn is_healthy(&self, now: Instant) -> bool {
+        match *self.0.last_ok.lock().unwrap() {
+            Some(last) => now.saturating_duration_since(last) <= self.0.window,
+            None => false,
+        }
+    }
+
+    /// serve the endpoint in background, returns bound address
+    pub async fn listen(&self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        info!("health endpoint on http://{}/healthz", addr);
+
+        let health = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(health.clone().respond(stream));
+                    }
+                    Err(err) => debug!("health accept error {}", err),
+                }
+            }
+        });
+
+        Ok(addr)
+    }
+
+    async fn respond(self, mut stream: TcpStream) {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+        while !buf.windows(4).any(|x| x == b"\r\n\r\n") {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+            if buf.len() > 8 * 1024 {
+                return;
+            }
+        }
+
+        let head = String::from_utf8_lossy(&buf);
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let (status, body) = match path {
+            "/healthz" if self.is_healthy(Instant::now()) => ("200 OK", "ok\n"),
+            "/healthz" => ("503 Service Unavailable", "stale\n"),
+            _ => ("404 Not Found", "not found\n"),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_fresh, Health};
+    use crate::metrics::Report;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_fresh() {
+        let mut report = Report::default();
+        // nothing new
+        assert!(is_fresh(Some(&report)));
+
+        report.fail("http");
+        assert!(!is_fresh(Some(&report)));
+
+        report.saved = 1;
+        assert!(is_fresh(Some(&report)));
+        assert!(!is_fresh(None));
+    }
+
+    #[tokio::test]
+    async fn test_healthz() {
+        let health = Health::new(Duration::from_secs(60));
+        let addr = health.listen("127.0.0.1:0").await.unwrap();
+        let client = reqwest::Client::new();
+        let status = |path: &str| {
+            let req = client.get(format!("http://{}{}", addr, path)).send();
+            async { req.await.unwrap().status().as_u16() }
+        };
+
+        // no run yet
+        assert_eq!(503, status("/healthz").await);
+
+        let now = Instant::now();
+        health.record(false, now);
+        assert_eq!(503, status("/healthz").await);
+
+        health.record(true, now);
+        assert_eq!(200, status("/healthz").await);
+        assert_eq!(404, status("/metrics").await);
+
+        // stuck runs
+        assert!(health.is_healthy(now + Duration::from_secs(60)));
+        assert!(!health.is_healthy(now + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,113 @@
+//! snapshot history of discovered lists
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+pub struct History {
+    dir: PathBuf,
+    /// snapshots to keep per list
+    keep: usize,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a, T> {
+    /// unix timestamp, unit: millisecond
+    time: u128,
+    list: &'a str,
+    images: &'a [T],
+}
+
+impl History {
+    pub fn new(dir: PathBuf, keep: usize) -> Self {
+        Self { dir, keep }
+    }
+
+    /// write snapshot of `list` as `<list name>-<time>.json`, then prune old ones
+    pub fn write<T: Serialize>(&self, list: &str, images: &[T]) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let prefix = snapshot_prefix(list);
+        let path = self.dir.join(format!("{}{:015}.json", prefix, time));
+        let snapshot = Snapshot { time, list, images };
+        std::fs::write(&path, serde_json::to_vec(&snapshot)?)?;
+
+        self.prune(&prefix)?;
+        Ok(path)
+    }
+
+    fn prune(&self, prefix: &str) -> io::Result<()> {
+        let mut snapshots = snapshots(&self.dir, prefix)?;
+        if snapshots.len() <= self.keep {
+            return Ok(());
+        }
+
+        // zero padded time, sort by name is sort by time
+        snapshots.sort();
+        let outdated = snapshots.len() - self.keep;
+        for path in snapshots.into_iter().take(outdated) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn snapshot_prefix(list: &str) -> String {
+    let name = Path::new(list)
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or("list");
+    format!("{}-", name)
+}
+
+fn snapshots(dir: &Path, prefix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matched = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| x.starts_with(prefix) && x.ends_with(".json"));
+        if matched {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{snapshots, History};
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(dir.path().to_path_buf(), 2);
+        let list = "/Data/js/obs_img/Observe_radar.js";
+
+        let mut written = Vec::new();
+        for i in 0..3 {
+            written.push(history.write(list, &[format!("frame_{}.png", i)]).unwrap());
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        history
+            .write("/Data/js/obs_img/Observe_sat.js", &["sat.jpg"])
+            .unwrap();
+
+        let mut kept = snapshots(dir.path(), "Observe_radar-").unwrap();
+        kept.sort();
+        assert_eq!(written[1..], kept);
+        assert_eq!(1, snapshots(dir.path(), "Observe_sat-").unwrap().len());
+
+        let latest: Value = serde_json::from_slice(&std::fs::read(&written[2]).unwrap()).unwrap();
+        assert_eq!(list, latest["list"]);
+        assert_eq!("frame_2.png", latest["images"][0]);
+    }
+}
@@ -0,0 +1,82 @@
+//! base url that list and image paths are resolved against
+use std::fmt;
+use url::Url;
+
+pub const DEFAULT: &str = "https://www.cwa.gov.tw";
+
+/// absolute http(s) url, always ends with `/`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host(Url);
+
+impl Default for Host {
+    fn default() -> Self {
+        parse(DEFAULT).unwrap()
+    }
+}
+
+impl Host {
+    pub fn join(&self, path: &str) -> Result<Url, url::ParseError> {
+        self.0.join(path)
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// `--host`/`CWA_HOST`
+pub fn parse(text: &str) -> Result<Host, String> {
+    let invalid = || format!("expect absolute http or https url. e.g. {}", DEFAULT);
+    let mut url = Url::parse(text.trim()).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(invalid());
+    }
+
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(Host(url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "https://www.cwa.gov.tw/",
+            parse(DEFAULT).unwrap().to_string()
+        );
+        assert_eq!(
+            "http://127.0.0.1:8080/cwa/",
+            parse("http://127.0.0.1:8080/cwa").unwrap().to_string()
+        );
+        assert_eq!(
+            "http://proxy/cwa/",
+            parse("http://proxy/cwa/").unwrap().to_string()
+        );
+
+        assert!(parse("www.cwa.gov.tw").is_err());
+        assert!(parse("ftp://www.cwa.gov.tw").is_err());
+        assert!(parse("file:///Data").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn test_join() {
+        let host = parse("http://proxy/cwa").unwrap();
+        assert_eq!(
+            "http://proxy/cwa/Data/",
+            host.join("Data/").unwrap().as_str()
+        );
+        assert_eq!("http://proxy/Data/", host.join("/Data/").unwrap().as_str());
+        assert_eq!(
+            "http://other/x.js",
+            host.join("http://other/x.js").unwrap().as_str()
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! download images of CWA (Central Weather Administration) lists
+// paths of `#[derive(CondKeys)]` inside this crate
+extern crate self as cwa_images;
+
+pub mod budget;
+pub mod chart;
+pub mod config;
+pub mod dedupe;
+pub mod duration;
+pub mod existing;
+#[cfg(feature = "faults")]
+pub mod fault;
+pub mod health;
+pub mod history;
+pub mod host;
+pub mod link;
+pub mod list_cache;
+pub mod logging;
+pub mod manifest;
+pub mod marker;
+pub mod matcher;
+pub mod metrics;
+#[cfg(test)]
+mod mock;
+pub mod parser;
+pub mod refresh;
+pub mod request;
+pub mod retention;
+pub mod schedule;
+pub mod shutdown;
+pub mod source;
+pub mod task;
+pub mod timestamp;
+pub mod typhoon;
+pub mod update;
+
+pub use host::Host;
+pub use request::CwaClient;
+pub use source::{Source, SOURCES};
+pub use task::{Fetched, Img, RunOptions, Schema, Task};
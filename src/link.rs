@@ -0,0 +1,68 @@
+//! stable path to the newest image of a task, for dashboards
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{remove_file, rename};
+
+/// `latest_<task name>.<extension of image>`
+pub fn link_name(task: &str, image: &Path) -> String {
+    match image.extension() {
+        Some(ext) => format!("latest_{}.{}", task, ext.to_string_lossy()),
+        None => format!("latest_{}", task),
+    }
+}
+
+/// point `link` at `target`, replacing the old link in one rename. a
+/// relative symlink where supported, a copy otherwise
+pub async fn update(link: &Path, target: &Path) -> io::Result<()> {
+    let mut tmp = OsString::from(link.as_os_str());
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let _ = remove_file(&tmp).await;
+
+    #[cfg(unix)]
+    {
+        let dir = link.parent().unwrap_or(Path::new(""));
+        let relative = target.strip_prefix(dir).unwrap_or(target);
+        tokio::fs::symlink(relative, &tmp).await?;
+    }
+    #[cfg(not(unix))]
+    tokio::fs::copy(target, &tmp).await?;
+
+    rename(&tmp, link).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link_name, update};
+    use std::path::Path;
+
+    #[test]
+    fn test_link_name() {
+        let image = Path::new("2024/07/24/CV1_3600_202407241200.png");
+        assert_eq!("latest_radar.png", link_name("radar", image));
+        assert_eq!("latest_radar", link_name("radar", Path::new("CV1")));
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("latest_radar.png");
+        std::fs::create_dir_all(dir.path().join("2024/07/24")).unwrap();
+        let old = dir.path().join("2024/07/24/CV1_3600_202407241200.png");
+        let new = dir.path().join("2024/07/24/CV1_3600_202407241210.png");
+        std::fs::write(&old, "old").unwrap();
+        std::fs::write(&new, "new").unwrap();
+
+        update(&link, &old).await.unwrap();
+        assert_eq!("old", std::fs::read_to_string(&link).unwrap());
+        update(&link, &new).await.unwrap();
+        assert_eq!("new", std::fs::read_to_string(&link).unwrap());
+        #[cfg(unix)]
+        assert_eq!(
+            Path::new("2024/07/24/CV1_3600_202407241210.png"),
+            std::fs::read_link(&link).unwrap()
+        );
+        assert!(!dir.path().join("latest_radar.png.tmp").exists());
+    }
+}
@@ -0,0 +1,166 @@
+//! conditional GET of list files, parsed lists reused on 304
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const FILE_NAME: &str = "list_cache.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    items: Vec<T>,
+}
+
+/// parsed lists keyed by list url, with validators of their response
+#[derive(Debug)]
+pub struct ListCache<T> {
+    entries: Mutex<HashMap<String, Entry<T>>>,
+    /// kept across restarts, written by `flush`
+    file: Option<PathBuf>,
+}
+
+impl<T> Default for ListCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            file: None,
+        }
+    }
+}
+
+impl<T> ListCache<T> {
+    /// empty cache written to `file`, see `load`
+    pub fn persisted(file: PathBuf) -> Self {
+        Self {
+            file: Some(file),
+            ..Default::default()
+        }
+    }
+}
+
+impl<T: Clone> ListCache<T> {
+    /// add `If-None-Match`/`If-Modified-Since` of cached list
+    pub fn conditional(&self, key: &str, req: RequestBuilder) -> RequestBuilder {
+        let cache = self.entries.lock().unwrap();
+        let Some(entry) = cache.get(key) else {
+            return req;
+        };
+
+        let mut req = req;
+        if let Some(etag) = &entry.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        req
+    }
+
+    /// cached list, after a 304 response
+    pub fn get(&self, key: &str) -> Option<Vec<T>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|x| x.items.clone())
+    }
+
+    /// keep parsed list, if the response can be validated later
+    pub fn store(&self, key: &str, headers: &HeaderMap, items: &[T]) {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.to_string())
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+
+        let mut cache = self.entries.lock().unwrap();
+        if etag.is_none() && last_modified.is_none() {
+            cache.remove(key);
+            return;
+        }
+        cache.insert(
+            key.to_string(),
+            Entry {
+                etag,
+                last_modified,
+                items: items.to_vec(),
+            },
+        );
+    }
+}
+
+impl<T: DeserializeOwned> ListCache<T> {
+    /// lists of the file written by a previous run, none if missing
+    pub fn load(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let entries = match std::fs::read(file) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        *self.entries.lock().unwrap() = entries;
+        Ok(())
+    }
+}
+
+impl<T: Serialize> ListCache<T> {
+    /// replace the file by rename, a crash never leaves a partial one
+    pub fn flush(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec(&*self.entries.lock().unwrap())?;
+        let mut tmp = OsString::from(file.as_os_str());
+        tmp.push(".tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(FILE_NAME);
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+        let cache = ListCache::persisted(file.clone());
+        cache.load().unwrap();
+        cache.store("http://host/a.js", &headers, &["a.png".to_string()]);
+        // no validator, not cached
+        cache.store(
+            "http://host/b.js",
+            &HeaderMap::new(),
+            &["b.png".to_string()],
+        );
+        cache.flush().unwrap();
+
+        let restarted = ListCache::<String>::persisted(file);
+        restarted.load().unwrap();
+        assert_eq!(
+            Some(vec!["a.png".to_string()]),
+            restarted.get("http://host/a.js")
+        );
+        assert_eq!(None, restarted.get("http://host/b.js"));
+
+        // in memory only
+        assert!(ListCache::<String>::default().flush().is_ok());
+    }
+}
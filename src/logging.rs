@@ -0,0 +1,78 @@
+//! log output of the cli, colors and line format
+use chrono::Local;
+use clap::ValueEnum;
+use env_logger::WriteStyle;
+use log::{Level, LevelFilter};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Color {
+    /// when stderr is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// UTC timestamp, level and module
+    Full,
+    /// local time and level
+    Compact,
+    /// `<N>` priority prefix without timestamp, journald adds its own
+    Systemd,
+}
+
+/// syslog priority of journald
+pub fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+pub fn init(level: LevelFilter, color: Color, format: LogFormat) {
+    let mut logger = env_logger::builder();
+    logger.filter_level(level).write_style(match color {
+        Color::Auto => WriteStyle::Auto,
+        Color::Always => WriteStyle::Always,
+        Color::Never => WriteStyle::Never,
+    });
+
+    match format {
+        LogFormat::Full => {}
+        LogFormat::Compact => {
+            logger.format(|buf, record| {
+                let style = buf.default_level_style(record.level());
+                writeln!(
+                    buf,
+                    "{} {style}{:<5}{style:#} {}",
+                    Local::now().format("%H:%M:%S"),
+                    record.level(),
+                    record.args()
+                )
+            });
+        }
+        LogFormat::Systemd => {
+            logger.format(|buf, record| {
+                writeln!(buf, "<{}>{}", priority(record.level()), record.args())
+            });
+        }
+    }
+    logger.init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(3, priority(Level::Error));
+        assert_eq!(4, priority(Level::Warn));
+        assert_eq!(6, priority(Level::Info));
+        assert_eq!(7, priority(Level::Trace));
+    }
+}
@@ -1,277 +1,1353 @@
-use bytes::{Buf, Bytes};
-use clap::Parser;
-use futures_core::Stream;
-use futures_util::StreamExt;
-use lazy_static::lazy_static;
+use chrono::{Local, NaiveDateTime};
+use clap::{error::ErrorKind, ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use cwa_images::budget::{self, Budget};
+use cwa_images::config::{self, Config, TaskConfig};
+use cwa_images::dedupe::{self, Dedupe};
+use cwa_images::duration::{self, MIN_INTERVAL};
+use cwa_images::existing::{self, Existing, Force, RealFs};
+use cwa_images::health::{self, Health};
+use cwa_images::history::History;
+use cwa_images::host::{self, Host};
+use cwa_images::list_cache::{self, ListCache};
+use cwa_images::logging::{self, Color, LogFormat};
+use cwa_images::manifest::{self, Manifest, Mismatch};
+use cwa_images::marker::Marker;
+use cwa_images::matcher::{Filter, Matcher, Mode};
+use cwa_images::metrics::Metrics;
+use cwa_images::parser::{
+    find_objects_spanned, is_js_identifier, parse_source_spanned, schema_hints_at, value_to_js,
+};
+use cwa_images::refresh::Refresh;
+use cwa_images::request::{self, ClientOptions, CwaClient};
+use cwa_images::retention::{Retention, Trash};
+use cwa_images::schedule::{self, Schedule, Timetable};
+use cwa_images::shutdown::Shutdown;
+use cwa_images::source::{self, SOURCES};
+use cwa_images::task::{
+    self, check_dir, human_size, run_merged, Catchup, Fetched, Img, Order, RunOptions, Task,
+    MAX_CONCURRENCY,
+};
+use cwa_images::timestamp::{self, Window};
+use cwa_images::update;
 use log::{debug, error, info, warn, LevelFilter};
-use parser::{find_objects, parse_source, CondKeys};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::env;
+use reqwest::header::{HeaderName, HeaderValue};
+use std::collections::BTreeSet;
 use std::error::Error;
-use std::path::Path;
-use std::str::FromStr;
-use std::time::Duration;
-use tokio::fs::{remove_file, File};
-use tokio::io::AsyncWriteExt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time;
 use url::Url;
 
-mod parser;
-
-lazy_static! {
-    static ref CWA_HOST: String = env::var("CWA_HOST").unwrap_or(DEFAULT_CWA_HOST.to_string());
-}
-
-const DEFAULT_CWA_HOST: &str = "https://www.cwa.gov.tw";
-
-const OBSERVE_SAT_LIST: &str = "/Data/js/obs_img/Observe_sat.js";
-const OBSERVE_SAT_DIR: &str = "/Data/satellite/";
-
-const OBSERVE_RADAR_LIST: &str = "/Data/js/obs_img/Observe_radar.js";
-const OBSERVE_RADAR_DIR: &str = "/Data/radar/";
-
-const OBSERVE_RADAR_RAIN_LIST: &str = "/Data/js/obs_img/Observe_radar_rain.js";
-const OBSERVE_RADAR_RAIN_DIR: &str = "/Data/radar_rain/";
+const EXAMPLES: &str = "Examples:
+  cwa_images --sat-img LCC_TRGB images
+  cwa_images --radar-cloud CV1_3600 -i 600 fetch
+  cwa_images --radar-rain RCLY_3600 list
+  cwa_images fetch-files --task radar --dir images CV1_3600_202407241200.png
+  cwa_images list-sources
+  cwa_images completions bash";
 
 #[derive(Debug, Parser)]
+#[command(after_help = EXAMPLES)]
 struct Args {
-    #[arg(long, help = "download file with contain string")]
-    sat_img: Option<String>,
-    #[arg(long, help = "download file with contain string")]
-    radar_cloud: Option<String>,
-    #[arg(long, help = "download file with contain string. e.g. RCLY_3600")]
-    radar_rain: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 
     #[arg(
         long,
-        help = "download file with contain string",
+        global = true,
+        action = ArgAction::Append,
+        help = "download file with contain string, can be repeated. repeated values are one task matching any of them, so the list is fetched once"
+    )]
+    sat_img: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download file with contain string, can be repeated. repeated values are one task matching any of them"
+    )]
+    radar_cloud: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download file with contain string, can be repeated. repeated values are one task matching any of them. e.g. RCLY_3600"
+    )]
+    radar_rain: Vec<String>,
+    #[arg(long, global = true, action = ArgAction::Append, help = "download file with contain string, can be repeated")]
+    lightning: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download accumulated rainfall map with contain string, can be repeated. e.g. .QZT8. for 24 hours"
+    )]
+    rainfall: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download typhoon track potential image with contain string, can be repeated. e.g. _PTA_"
+    )]
+    typhoon: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download weather chart with contain string, can be repeated. e.g. SFC_ANALYSIS"
+    )]
+    weather_chart: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download surface analysis chart with contain string, can be repeated. e.g. SFCcombo"
+    )]
+    surface_chart: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download file with contain string, can be repeated, paired in order with --custom-list and --custom-dir",
         help_heading = "Custom",
         requires("custom_list"),
         requires("custom_dir")
     )]
-    custom: Option<String>,
+    custom: Vec<String>,
     #[arg(
         long,
+        global = true,
+        action = ArgAction::Append,
         help_heading = "Custom",
         help = "path of images list url. e.g. /Data/js/obs_img/Observe_lightning.js"
     )]
-    custom_list: Option<String>,
+    custom_list: Vec<String>,
     #[arg(
         long,
+        global = true,
+        action = ArgAction::Append,
         help_heading = "Custom",
         help = "path of images dir. e.g. /Data/lightning/"
     )]
-    custom_dir: Option<String>,
+    custom_dir: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help_heading = "Custom",
+        help = "task name for logs and --per-task-dirs, paired in order with --custom [default: custom, or custom_N when repeated]"
+    )]
+    custom_name: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "skip file with contain string, same mode as filters, can be repeated"
+    )]
+    exclude: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "also require file name to contain any of these strings, for all tasks, can be repeated"
+    )]
+    contains_any: Vec<String>,
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "also require file name to contain all of these strings, for all tasks, can be repeated"
+    )]
+    contains_all: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        action = ArgAction::Append,
+        help = "download file with description text containing this string, together with filters, can be repeated. e.g. 彩色"
+    )]
+    text_contains: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "load tasks from toml file, merged with tasks of flags"
+    )]
+    config: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "load tasks from json array of {list, dir, contains, name, ...} like the config file, - for stdin. merged with tasks of flags"
+    )]
+    tasks: Option<String>,
 
     #[arg(default_value = "images", help = "download dir")]
     dir: String,
 
     #[arg(
         long,
-        short,
-        default_value = "0",
-        help = "job interval, unit: second, 0 is disable"
+        global = true,
+        help = "save images of each task into <DIR>/<task name>/"
+    )]
+    per_task_dirs: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "sat_img",
+        help_heading = "Task dirs",
+        help = "save images of --sat-img into <DIR> instead of the download dir"
+    )]
+    sat_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "radar_cloud",
+        help_heading = "Task dirs",
+        help = "save images of --radar-cloud into <DIR>"
+    )]
+    radar_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "radar_rain",
+        help_heading = "Task dirs",
+        help = "save images of --radar-rain into <DIR>"
+    )]
+    radar_rain_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "lightning",
+        help_heading = "Task dirs",
+        help = "save images of --lightning into <DIR>"
+    )]
+    lightning_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "rainfall",
+        help_heading = "Task dirs",
+        help = "save images of --rainfall into <DIR>"
+    )]
+    rainfall_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "typhoon",
+        help_heading = "Task dirs",
+        help = "save images of --typhoon into <DIR>"
+    )]
+    typhoon_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "weather_chart",
+        help_heading = "Task dirs",
+        help = "save images of --weather-chart into <DIR>"
+    )]
+    weather_chart_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        requires = "surface_chart",
+        help_heading = "Task dirs",
+        help = "save images of --surface-chart into <DIR>"
+    )]
+    surface_chart_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "save images into YYYY/MM/DD/ by the time in file name, files without time are not moved"
+    )]
+    date_subdirs: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "point <DIR>/latest_<task name>.<ext> at the newest file saved by each task, a copy where symlinks are not supported"
+    )]
+    latest_link: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = duration::parse,
+        help = "after each cycle, delete files of each task older than this, by the time in file name or mtime. e.g. 7d"
+    )]
+    max_age: Option<Duration>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        help = "after each cycle, delete files of each task except the newest N"
+    )]
+    keep_files: Option<usize>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "move files deleted by --max-age or --keep-files into <DIR>/<YYYY-MM-DD>/ of the day instead, copied then deleted when on another file system"
+    )]
+    trash_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        requires = "trash_dir",
+        value_parser = duration::parse,
+        help = "delete days of --trash-dir older than this for good. e.g. 30d"
+    )]
+    trash_max_age: Option<Duration>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value_t = existing::DEFAULT_SCAN_LIMIT,
+        help = "read existing file names of a dir once per cycle if it has no more entries than this, otherwise check each file. 0 is always check each file"
+    )]
+    scan_limit: usize,
+
+    #[arg(
+        long,
+        short,
+        global = true,
+        default_value = "0",
+        value_parser = duration::parse,
+        help = "job interval, seconds or e.g. 30s, 10m, 1h30m. 0 is run once"
+    )]
+    interval: Duration,
+
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "interval",
+        value_parser = schedule::parse,
+        help = "run at times of cron expression in local time instead of --interval, slots missed by a long cycle are skipped. e.g. '*/10 * * * *'"
+    )]
+    cron: Option<Schedule>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        value_parser = duration::parse,
+        help = "wait a random delay up to this before each cycle, so instances don't hit the server at once. seconds or e.g. 30s"
+    )]
+    jitter: Duration,
+
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "max-cycles",
+        value_name = "N",
+        help = "exit after N cycles, waiting --interval or --cron between them. default or 0 is once with --interval 0, forever otherwise"
+    )]
+    cycles: Option<usize>,
+
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "match-regex",
+        help = "treat contain strings as regular expressions"
+    )]
+    regex: bool,
+
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "regex",
+        help = "treat contain strings as glob patterns of file name. e.g. 's01q*.jpg'"
+    )]
+    glob: bool,
+
+    #[arg(
+        long,
+        global = true,
+        env = "CWA_HOST",
+        default_value = host::DEFAULT,
+        value_parser = host::parse,
+        help = "base url of lists and images, for a mirror or caching proxy"
+    )]
+    host: Host,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "10",
+        value_parser = duration::parse,
+        help = "give up connecting after this, seconds or e.g. 1m. 0 is no timeout"
+    )]
+    connect_timeout: Duration,
+    #[arg(
+        long,
+        global = true,
+        default_value = "30",
+        value_parser = duration::parse,
+        help = "give up a list or image if no data is received for this long, seconds or e.g. 1m. 0 is no timeout"
+    )]
+    read_timeout: Duration,
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        value_parser = duration::parse,
+        help = "give up a list or image not complete in this long, including the body, seconds or e.g. 5m. 0 is no timeout"
+    )]
+    timeout: Duration,
+    #[arg(
+        long,
+        global = true,
+        default_value = "2",
+        help = "retry a list or image this many times on timeout, connection error or 5xx, waiting 1s, 2s, 4s... in between"
+    )]
+    retries: u32,
+    #[arg(
+        long,
+        global = true,
+        value_name = "REQUESTS_PER_SECOND",
+        value_parser = request::parse_rate,
+        help = "send at most this many requests per second over all tasks and downloads, e.g. 0.5 for one every 2s"
+    )]
+    rate_limit: Option<f64>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "BYTES_PER_SECOND",
+        value_parser = budget::parse_bytes,
+        help = "download images at most this fast over all tasks and downloads, e.g. 200K or 1.5M. a single chunk may go over"
+    )]
+    limit_rate: Option<u64>,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = request::parse_proxy,
+        help = "send all requests through http or socks5 proxy, instead of HTTP_PROXY/HTTPS_PROXY. e.g. socks5h://127.0.0.1:1080"
+    )]
+    proxy: Option<Url>,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = request::parse_header_value,
+        help = "User-Agent of lists and images requests"
+    )]
+    user_agent: Option<HeaderValue>,
+    #[arg(
+        long,
+        global = true,
+        value_parser = request::parse_header,
+        help = "extra request header, can be repeated. e.g. 'Referer: https://www.cwa.gov.tw/'"
+    )]
+    header: Vec<(HeaderName, HeaderValue)>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "allow list entries to resolve to another host than --host"
+    )]
+    allow_cross_host: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "match keys of list objects case-insensitively"
+    )]
+    ignore_key_case: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "download at most N newest missing files per task on first run, then no limit"
+    )]
+    catchup_limit: Option<usize>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        help = "download at most N new files per task in a cycle, the rest in later cycles"
+    )]
+    max_files: Option<usize>,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = budget::parse_bytes,
+        help = "stop starting downloads in a cycle once all tasks saved this many bytes, the rest in later cycles. e.g. 500M, 2G"
+    )]
+    max_bytes: Option<u64>,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = budget::parse_bytes,
+        help = "skip image larger than this by Content-Length, or abort it past this size. e.g. 50M"
+    )]
+    max_size: Option<u64>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "show sizes in logs in units of 1000 instead of 1024"
+    )]
+    si: bool,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "download this many images at a time, up to 16"
+    )]
+    concurrency: u64,
+    #[arg(
+        long,
+        global = true,
+        value_parser = duration::parse,
+        help = "start each run with one download at a time and grow to --concurrency over this, seconds or e.g. 30s"
+    )]
+    ramp_up: Option<Duration>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "download existing files again once, e.g. to repair truncated files. the old file is kept until the new one is complete"
+    )]
+    force: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "request existing files again with If-Modified-Since of their mtime, overwritten only if changed on the server, e.g. corrected charts. mtime of saved files is set to Last-Modified"
+    )]
+    refresh: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "hard link an image saved again under another name to the first copy, by blake3 hash of content. the index is kept in dedupe.json of the dir"
+    )]
+    dedupe: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "append saved files of each cycle to manifest.json in the dir of each task"
+    )]
+    manifest: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "keep ETag/Last-Modified and parsed lists in list_cache.json of the dir, so unchanged lists are not downloaded again after restart"
+    )]
+    list_cache: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "rewrite metrics for node_exporter textfile collector after each cycle. e.g. /var/lib/node_exporter/cwa_images.prom"
+    )]
+    metrics_textfile: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        help = "skip image narrower than this, unit: pixel"
+    )]
+    min_width: usize,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        help = "skip image shorter than this, unit: pixel"
+    )]
+    min_height: usize,
+
+    #[arg(
+        long,
+        global = true,
+        help = "read dimensions by range request before full download, for --min-width/--min-height"
+    )]
+    probe_dimensions: bool,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        value_parser = duration::parse,
+        help = "give up the rest of a cycle after this, seconds or e.g. 5m. 0 is no timeout"
+    )]
+    cycle_timeout: Duration,
+
+    #[arg(
+        long,
+        global = true,
+        help = "download matched files of all tasks as one sequence sorted by file name time"
+    )]
+    merge_tasks_output: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "signal complete files to directory watchers, after flushed to disk"
+    )]
+    write_marker: Option<Marker>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "print entries of each task's list as json and exit, no download"
+    )]
+    list_only: bool,
+
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "list_only",
+        help = "print files that would be downloaded and whether they exist, then exit"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        conflicts_with_all = ["list_only", "dry_run"],
+        help = "print urls of files not downloaded yet, one per line, then exit. e.g. for aria2c -i -"
+    )]
+    print_urls: bool,
+
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "limit",
+        value_name = "N",
+        help = "only the newest N matched files per task, by the time in file name, or list order if any has none"
+    )]
+    latest: Option<usize>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "list",
+        help = "download order of matched files, newest first gets the most recent frames under --max-files or --max-bytes"
+    )]
+    order: Order,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = timestamp::parse_since,
+        help = "only files with time in file name since, Asia/Taipei time. e.g. 2024-07-24 or 2024-07-24T00:00"
+    )]
+    since: Option<NaiveDateTime>,
+    #[arg(
+        long,
+        global = true,
+        value_parser = timestamp::parse_until,
+        help = "only files with time in file name before, date only is the whole day. e.g. 2024-07-25T12:00"
+    )]
+    until: Option<NaiveDateTime>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "check magic bytes of jpeg, png or gif before saving, to skip html error pages"
+    )]
+    verify_image: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "send HEAD for existing files and download again if the size differs from Content-Length, e.g. to repair truncated files"
+    )]
+    verify_size: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "serve /healthz on address. e.g. 127.0.0.1:9898"
+    )]
+    health_listen: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        default_value = "3600",
+        value_parser = duration::parse,
+        help = "/healthz fails if no successful run within this, seconds or e.g. 15m"
+    )]
+    freshness_window: Duration,
+
+    #[arg(
+        long,
+        global = true,
+        help = "write discovered lists of each run into dir"
+    )]
+    list_history: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "100",
+        help = "snapshots of list history to keep per list"
+    )]
+    list_history_keep: usize,
+
+    #[arg(
+        long,
+        global = true,
+        help = "check newer release at startup. e.g. GitHub releases api url"
+    )]
+    update_check_url: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        global = true,
+        action = ArgAction::Count,
+        help = "print debug messages, -vv also traces http requests"
+    )]
+    verbose: u8,
+
+    #[arg(
+        long,
+        short,
+        global = true,
+        conflicts_with_all = ["verbose", "debug"],
+        help = "print warnings and errors only"
+    )]
+    quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "auto",
+        help = "colored log output"
+    )]
+    color: Color,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "full",
+        help = "log line format, systemd for journald priorities"
     )]
-    interval: u64,
+    log_format: LogFormat,
 
-    #[arg(long, short, help = "print debug message")]
+    /// old name of a single -v
+    #[arg(long, short, global = true, hide = true)]
     debug: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Img {
-    img: String,
-    text: String,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Download matched images of tasks, the default without subcommand
+    Fetch,
+    /// Print entries of each task's list as json, same as --list-only
+    List,
+    /// Print built-in sources with their list and image paths
+    ListSources {
+        #[arg(long, help = "print as json")]
+        json: bool,
+    },
+    /// Download given files of a task without fetching the list
+    FetchFiles {
+        #[arg(long, help = "built-in source name, see list-sources. e.g. radar")]
+        task: String,
+        #[arg(long, default_value = "images", help = "download dir")]
+        dir: String,
+        #[arg(required = true, help = "file names. e.g. CV1_3600_202405011230.png")]
+        names: Vec<String>,
+    },
+    /// Delete files of tasks beyond --max-age or --keep-files without downloading
+    Prune,
+    /// Move the newest copy of a file in --trash-dir back
+    Restore {
+        #[arg(
+            long,
+            help = "dir to put the file back into, the download dir if not given"
+        )]
+        dir: Option<String>,
+        #[arg(help = "file name. e.g. CV1_3600_202405011230.png")]
+        name: String,
+    },
+    /// Check files recorded in manifest.json still exist with the same size and blake3 hash
+    Verify,
+    /// Print where each image entry is in a list file, for debugging format changes
+    Parse {
+        #[arg(help = "local list file. e.g. Observe_radar.js")]
+        file: String,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "write parsed value back as minified js"
+        )]
+        emit_js: Option<String>,
+        #[arg(long, default_value = "data", help = "variable name of --emit-js")]
+        js_var: String,
+    },
+    /// Print shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print man page in roff
+    Manpage,
 }
 
-impl Img {
-    async fn download(
-        &self,
-        client: &mut Client,
-        dir: &str,
-    ) -> Result<reqwest::Response, Box<dyn Error>> {
-        let url = Url::from_str(&CWA_HOST)?.join(dir)?.join(&self.img)?;
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if let Err(err) = args.validate() {
+        err.exit();
+    }
+
+    logging::init(args.log_level(), args.color, args.log_format);
 
-        // tf?
-        Ok(client.get(url).send().await?.error_for_status()?)
+    if !args.interval.is_zero() && args.interval < MIN_INTERVAL {
+        warn!(
+            "interval {} is shorter than {}, please be gentle to the site",
+            humantime::format_duration(args.interval),
+            humantime::format_duration(MIN_INTERVAL)
+        );
     }
 
-    fn filename(&self) -> &str {
-        Path::new(&self.img).file_name().unwrap().to_str().unwrap()
+    // dry run leaves no files behind
+    let list_cache = if args.list_cache && !args.dry_run && !args.print_urls {
+        ListCache::persisted(Path::new(&args.dir).join(list_cache::FILE_NAME))
+    } else {
+        ListCache::default()
+    };
+    if let Err(err) = list_cache.load() {
+        warn!(
+            "cannot read {} {}, starting empty",
+            list_cache::FILE_NAME,
+            err
+        );
     }
-}
+    let dedupe = (args.dedupe && !args.dry_run && !args.print_urls)
+        .then(|| Dedupe::new(Path::new(&args.dir).join(dedupe::FILE_NAME)));
+    if let Some(Err(err)) = dedupe.as_ref().map(|x| x.load()) {
+        warn!("cannot read {} {}, starting empty", dedupe::FILE_NAME, err);
+    }
+    let opts = RunOptions {
+        allow_cross_host: args.allow_cross_host,
+        ignore_key_case: args.ignore_key_case,
+        // dry run leaves no files behind
+        history: args
+            .list_history
+            .as_ref()
+            .filter(|_| !args.dry_run && !args.print_urls)
+            .map(|x| History::new(x.into(), args.list_history_keep)),
+        shutdown: Shutdown::default(),
+        min_width: args.min_width,
+        min_height: args.min_height,
+        probe_dimensions: args.probe_dimensions,
+        write_marker: args.write_marker,
+        per_task_dirs: args.per_task_dirs,
+        date_subdirs: args.date_subdirs,
+        latest_link: args.latest_link,
+        verify_image: args.verify_image,
+        verify_size: args.verify_size,
+        latest: args.latest,
+        window: Window {
+            since: args.since,
+            until: args.until,
+        },
+        existing: Existing::new(Box::new(RealFs), args.scan_limit),
+        list_cache,
+        retention: Retention {
+            max_age: args.max_age,
+            keep_files: args.keep_files,
+            trash: args.trash_dir.clone().map(|dir| Trash {
+                dir,
+                max_age: args.trash_max_age,
+            }),
+        },
+        max_files: args.max_files,
+        max_bytes: Budget::new(args.max_bytes),
+        manifest: args.manifest.then(Manifest::default),
+        max_size: args.max_size,
+        force: args.force.then(Force::default),
+        refresh: args.refresh.then(Refresh::default),
+        dedupe,
+        order: args.order,
+        concurrency: (args.concurrency as usize).min(MAX_CONCURRENCY),
+        ramp_up: args.ramp_up,
+        si: args.si,
+    };
+    opts.shutdown.listen();
+    if args.concurrency as usize > MAX_CONCURRENCY {
+        warn!(
+            "concurrency {} is capped at {}",
+            args.concurrency, MAX_CONCURRENCY
+        );
+    }
+    if opts.concurrency > 1 {
+        info!("downloading up to {} images at a time", opts.concurrency);
+    }
+    if let Some(rate) = args.limit_rate {
+        info!(
+            "download rate limited to {}/s",
+            human_size(rate as usize, opts.si)
+        );
+    }
+    let client_opts = ClientOptions {
+        connect_timeout: args.connect_timeout,
+        read_timeout: args.read_timeout,
+        timeout: args.timeout,
+        user_agent: args.user_agent.clone(),
+        headers: args.header.clone(),
+        proxy: args.proxy.clone(),
+        retries: args.retries,
+        rate_limit: args.rate_limit,
+        limit_rate: args.limit_rate,
+    };
+    let client = CwaClient::build(args.host.clone(), &client_opts).unwrap_or_else(|err| {
+        error!("cannot create http client {}", err);
+        process::exit(2);
+    });
 
-impl CondKeys for Img {
-    fn keys<'a>() -> &'a [&'a str] {
-        &["img", "text"]
+    // no request is made by local commands
+    let local = matches!(
+        args.command,
+        Some(
+            Command::Completions { .. }
+                | Command::Manpage
+                | Command::ListSources { .. }
+                | Command::Parse { .. }
+                | Command::Prune
+                | Command::Restore { .. }
+                | Command::Verify
+        )
+    );
+    if let (false, Some(proxy)) = (local, &client_opts.proxy) {
+        if let Err(err) = request::check_proxy(proxy, args.connect_timeout).await {
+            error!("{}", err);
+            process::exit(2);
+        }
     }
-}
 
-#[derive(Debug)]
-struct Task {
-    list: String,
-    dir: String,
-    contains: String,
-}
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            write_completions(*shell, &mut io::stdout());
+            return;
+        }
+        Some(Command::Manpage) => {
+            write_manpage(&mut io::stdout()).expect("cannot write man page");
+            return;
+        }
+        Some(Command::ListSources { json }) => {
+            if *json {
+                println!("{}", serde_json::to_string_pretty(SOURCES).unwrap());
+            } else {
+                print!("{}", source::table());
+            }
+            return;
+        }
+        _ => {}
+    }
 
-impl Task {
-    fn new(list: String, dir: String, contains: String) -> Self {
-        Self {
-            list,
-            dir,
-            contains,
+    if let Some(Command::Parse {
+        file,
+        emit_js,
+        js_var,
+    }) = &args.command
+    {
+        let emit = emit_js.as_ref().map(|x| (Path::new(x), js_var.as_str()));
+        if let Err(err) = parse_file(Path::new(file), args.ignore_key_case, emit) {
+            error!("{}", err);
+            process::exit(1);
         }
+        return;
     }
 
-    fn new_sat(contains: String) -> Self {
-        Self::new(
-            OBSERVE_SAT_LIST.to_string(),
-            OBSERVE_SAT_DIR.to_string(),
-            contains,
-        )
+    if let Some(Command::Restore { dir, name }) = &args.command {
+        let Some(trash) = &opts.retention.trash else {
+            error!("restore needs --trash-dir");
+            process::exit(2);
+        };
+        if !is_safe_filename(name) {
+            error!("invalid file name {:?}", name);
+            process::exit(2);
+        }
+        let dir = Path::new(dir.as_ref().unwrap_or(&args.dir));
+        match trash.restore(name, dir) {
+            Ok(trashed) => info!(
+                "restored {} from {}",
+                dir.join(name).to_str().unwrap(),
+                trashed.to_str().unwrap()
+            ),
+            Err(err) => {
+                error!("cannot restore {} {}", name, err);
+                process::exit(1);
+            }
+        }
+        return;
     }
 
-    fn new_radar(contains: String) -> Self {
-        Self::new(
-            OBSERVE_RADAR_LIST.to_string(),
-            OBSERVE_RADAR_DIR.to_string(),
-            contains,
-        )
+    if let Some(Command::FetchFiles { task, dir, names }) = args.command {
+        let Some(task) = Task::from_name(&task, Matcher::Contains(Vec::new())) else {
+            error!("unknown task {}", task);
+            process::exit(2);
+        };
+        check_dir(Path::new(&dir)).expect("can not create dir");
+
+        let failed = fetch_files(&client, &task, &names, Path::new(&dir), &opts)
+            .await
+            .unwrap_or_else(|err| {
+                error!("{}", err);
+                process::exit(2);
+            });
+        flush_manifest(&opts);
+        if failed > 0 {
+            error!("{} of {} files failed", failed, names.len());
+            process::exit(1);
+        }
+        return;
     }
 
-    fn new_radar_rain(contains: String) -> Self {
-        Self::new(
-            OBSERVE_RADAR_RAIN_LIST.to_string(),
-            OBSERVE_RADAR_RAIN_DIR.to_string(),
-            contains,
-        )
+    // create task
+    let tasks = build_tasks(&args).unwrap_or_else(|err| {
+        error!("{}", err);
+        process::exit(2);
+    });
+    // would delete any file of its dir, e.g. of other tasks
+    if opts.retention.is_set() {
+        if let Some(task) = tasks.iter().find(|x| x.contains.matches_all()) {
+            error!(
+                "{} matches every file, give it a filter to use --max-age or --keep-files",
+                task.name
+            );
+            process::exit(2);
+        }
     }
 
-    async fn download_list(&self, client: &mut Client) -> Result<Vec<Img>, Box<dyn Error>> {
-        info!("download list");
-        let url = Url::from_str(&CWA_HOST)?.join(&self.list)?;
-        debug!("list url {}", url);
-        let source = client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
-        let object = parse_source(&source)?;
-        Ok(find_objects(object))
-    }
-
-    async fn run(&self, client: &mut Client, out_dir: &Path) -> Result<(), Box<dyn Error>> {
-        let image_list = self.download_list(client).await?;
-        let target_imgs_iter = image_list.iter().filter(|x| x.img.contains(&self.contains));
-
-        for img in target_imgs_iter {
-            let dest = out_dir.join(img.filename());
-            // skip exists file
-            if dest.is_file() {
-                debug!("skiped {}", dest.to_str().unwrap());
-                continue;
-            } else if dest.exists() {
-                return Err(format!("{} is directory", dest.to_str().unwrap()).into());
+    if args.list_only || matches!(args.command, Some(Command::List)) {
+        let mut lists = Vec::new();
+        for task in &tasks {
+            match task.list_entries(&client, &opts).await {
+                Ok(list) => lists.push(list),
+                Err(err) => {
+                    error!("{}", err);
+                    process::exit(1);
+                }
             }
+        }
+        println!("{}", serde_json::to_string_pretty(&lists).unwrap());
+        return;
+    }
 
-            let Ok(resp) = img.download(client, &self.dir).await else {
-                continue;
-            };
+    if matches!(args.command, Some(Command::Prune)) {
+        if !opts.retention.is_set() {
+            error!("prune needs --max-age or --keep-files");
+            process::exit(2);
+        }
+        let images_dir = Path::new(&args.dir);
+        for task in &tasks {
+            match task.prune(images_dir, &opts) {
+                Ok(count) => info!("pruned {} files of {}", count, task.name),
+                Err(err) => warn!("cannot prune images of {} {}", task.name, err),
+            }
+        }
+        flush_manifest(&opts);
+        return;
+    }
 
-            let Ok(resp_no_err) = resp.error_for_status() else {
-                continue;
-            };
+    if matches!(args.command, Some(Command::Verify)) {
+        let images_dir = Path::new(&args.dir);
+        let dirs: BTreeSet<PathBuf> = tasks.iter().map(|x| x.out_dir(images_dir, &opts)).collect();
+        let mut failed = 0;
+        for dir in dirs {
+            let mismatches = manifest::verify(&dir).unwrap_or_else(|err| {
+                error!("cannot read manifest of {} {}", dir.to_str().unwrap(), err);
+                process::exit(2);
+            });
+            for (entry, mismatch) in &mismatches {
+                let path = dir.join(&entry.file);
+                match mismatch {
+                    Mismatch::Missing => println!("missing\t{}", path.to_str().unwrap()),
+                    Mismatch::Size { actual } => println!(
+                        "size\t{}\t{} != {}",
+                        path.to_str().unwrap(),
+                        actual,
+                        entry.size
+                    ),
+                    Mismatch::Checksum { actual } => println!(
+                        "blake3\t{}\t{} != {}",
+                        path.to_str().unwrap(),
+                        actual,
+                        entry.blake3.as_deref().unwrap_or_default()
+                    ),
+                }
+            }
+            failed += mismatches.len();
+        }
+        if failed > 0 {
+            error!("{} files do not match manifest", failed);
+            process::exit(1);
+        }
+        return;
+    }
 
-            let mut stream = resp_no_err.bytes_stream();
-            match save_stream(&dest, &mut stream).await {
-                Ok(size) => {
-                    info!(
-                        "saved {} {}",
-                        dest.to_str().unwrap(),
-                        human_size(size as usize)
-                    );
+    if args.dry_run {
+        let images_dir = Path::new(&args.dir);
+        let limit = Catchup::new(args.catchup_limit).next_limit();
+        for task in &tasks {
+            match task.dry_run(&client, images_dir, &opts, limit).await {
+                Ok(targets) => {
+                    for (img, exists) in targets {
+                        let state = if exists { "exists" } else { "new" };
+                        println!("{}\t{}\t{}\t{}", task.name, state, img.img, img.text);
+                    }
                 }
                 Err(err) => {
-                    warn!("cannot save file {}", err);
-                    let _ = remove_file(dest).await;
+                    error!("{}", err);
+                    process::exit(1);
                 }
             }
         }
-
-        Ok(())
+        return;
     }
-}
-
-#[tokio::main]
-async fn main() {
-    let args = Args::parse();
-
-    let mut logger = env_logger::builder();
 
-    if args.debug {
-        logger.filter_level(LevelFilter::Debug);
-    } else {
-        logger.filter_level(LevelFilter::Info);
+    if args.print_urls {
+        let images_dir = Path::new(&args.dir);
+        let limit = Catchup::new(args.catchup_limit).next_limit();
+        for task in &tasks {
+            match task.urls(&client, images_dir, &opts, limit).await {
+                Ok(urls) => {
+                    for url in urls {
+                        println!("{}", url);
+                    }
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        return;
     }
 
-    logger.init();
-
     // setup dir
     debug!("setup dir...");
     let images_dir = Path::new(&args.dir);
     check_dir(images_dir).expect("can not create dir");
-
-    // create task
-    let mut tasks = Vec::new();
-
-    if let Some(sat) = args.sat_img {
-        tasks.push(Task::new_sat(sat));
+    for dir in tasks.iter().filter_map(|x| x.save_dir.as_ref()) {
+        check_dir(dir).unwrap_or_else(|err| {
+            error!("can not create dir {} {}", dir.display(), err);
+            process::exit(2);
+        });
     }
 
-    if let Some(radar) = args.radar_cloud {
-        tasks.push(Task::new_radar(radar));
+    if let Some(url) = args.update_check_url {
+        // don't block startup
+        tokio::spawn(update::check(client.http.clone(), url));
     }
 
-    if let Some(radar_rain) = args.radar_rain {
-        tasks.push(Task::new_radar_rain(radar_rain));
-    }
-
-    if let Some(custom) = args.custom {
-        tasks.push(Task::new(
-            args.custom_list.expect("list args required"),
-            args.custom_dir.expect("dir args required"),
-            custom,
-        ))
+    let health = Health::new(args.freshness_window);
+    if let Some(addr) = &args.health_listen {
+        if let Err(err) = health.listen(addr).await {
+            error!("cannot listen on {} {}", addr, err);
+            process::exit(2);
+        }
     }
 
-    let cycle_time = if args.interval != 0 {
-        Duration::from_secs(args.interval)
-    } else {
-        // dummy interval
-        Duration::from_secs(3600)
-    };
-    let mut interval = time::interval(cycle_time);
-
-    let mut client = Client::new();
+    let once = args.interval.is_zero() && args.cron.is_none();
+    // tasks without own interval follow --interval
+    let intervals = tasks
+        .iter()
+        .map(|x| x.interval.unwrap_or(args.interval))
+        .collect();
+    let mut timetable = Timetable::new(intervals, time::Instant::now());
+    // own intervals of tasks under --cron, checked on each slot
+    let mut last_runs = vec![None; tasks.len()];
+    let mut metrics = Metrics::default();
+    let mut catchup = Catchup::new(args.catchup_limit);
 
+    let mut last_slot = None;
+    // 0 is no limit, same as absent
+    let max_cycles = args.cycles.filter(|x| *x > 0);
+    let mut cycles = 0;
     loop {
-        interval.tick().await;
+        let slot = match &args.cron {
+            Some(cron) => match cron.next_slot(last_slot.as_ref(), &Local::now()) {
+                Some((slot, skipped)) => {
+                    if skipped > 0 {
+                        warn!("last cycle overran {} scheduled runs", skipped);
+                    }
+                    info!("next run at {}", slot.format("%Y-%m-%d %H:%M:%S"));
+                    Some(slot)
+                }
+                None => {
+                    error!("no next time of --cron");
+                    break;
+                }
+            },
+            None => None,
+        };
+        let wait = async {
+            let now = match &slot {
+                Some(slot) => {
+                    time::sleep(schedule::until(slot)).await;
+                    time::Instant::now()
+                }
+                None => {
+                    // without task, an empty cycle each interval
+                    let next = timetable.next().unwrap_or_else(|| {
+                        time::Instant::now() + if once { Duration::ZERO } else { args.interval }
+                    });
+                    time::sleep_until(next).await;
+                    next
+                }
+            };
+            // due tasks are still checked by the planned time
+            let jitter = schedule::jitter(args.jitter);
+            if !args.jitter.is_zero() {
+                debug!("jitter {}", humantime::format_duration(jitter));
+                time::sleep(jitter).await;
+            }
+            now
+        };
+        let now = tokio::select! {
+            now = wait => now,
+            _ = opts.shutdown.wait() => break,
+        };
+        last_slot = slot;
+        let limit = catchup.next_limit();
 
         info!("run tasks");
-        for task in &tasks {
-            match task.run(&mut client, images_dir).await {
-                Ok(_) => {}
-                Err(err) => {
-                    error!("{}", err)
+        opts.existing.clear();
+        opts.max_bytes.reset();
+        if let Some(marker) = opts.write_marker {
+            let dirs: BTreeSet<PathBuf> =
+                tasks.iter().map(|x| x.out_dir(images_dir, &opts)).collect();
+            for dir in dirs {
+                if let Err(err) = marker.reset(&dir).await {
+                    warn!("cannot reset marker {}", err);
+                }
+            }
+        }
+
+        let mut due = Vec::new();
+        if args.cron.is_some() {
+            for (task, last_run) in tasks.iter().zip(&mut last_runs) {
+                if !task.is_due(*last_run, now) {
+                    debug!("{} not due", task.name);
+                    continue;
+                }
+                *last_run = Some(now);
+                due.push(task);
+            }
+        } else {
+            due.extend(timetable.take_due(now).into_iter().map(|i| &tasks[i]));
+        }
+
+        let cycle = async {
+            // cycles without due task don't count as a run
+            let mut fresh = !due.is_empty();
+            if args.merge_tasks_output {
+                let reports = run_merged(&due, &client, images_dir, &opts, limit).await;
+                for (task, report) in due.iter().zip(reports) {
+                    fresh &= health::is_fresh(report.as_ref());
+                    metrics.record(&task.name, report.as_ref(), unix_now());
+                }
+            } else {
+                for task in due {
+                    if opts.shutdown.is_requested() {
+                        break;
+                    }
+
+                    match task.run(&client, images_dir, &opts, limit).await {
+                        Ok(report) => {
+                            fresh &= health::is_fresh(Some(&report));
+                            metrics.record(&task.name, Some(&report), unix_now());
+                        }
+                        Err(err) => {
+                            error!("{}", err);
+                            fresh = false;
+                            metrics.record(&task.name, None, unix_now());
+                        }
+                    }
                 }
             }
+            fresh
+        };
+        // dropping the cycle cancels the download in flight, its .part is removed
+        let fresh = if args.cycle_timeout.is_zero() {
+            cycle.await
+        } else {
+            time::timeout(args.cycle_timeout, cycle)
+                .await
+                .unwrap_or_else(|_| {
+                    warn!(
+                        "cycle timed out after {}, remaining files wait for next cycle",
+                        humantime::format_duration(args.cycle_timeout)
+                    );
+                    false
+                })
+        };
+        health.record(fresh, std::time::Instant::now());
+        flush_manifest(&opts);
+        if let Err(err) = opts.list_cache.flush() {
+            warn!("cannot write {} {}", list_cache::FILE_NAME, err);
+        }
+        if let Some(Err(err)) = opts.dedupe.as_ref().map(|x| x.flush()) {
+            warn!("cannot write {} {}", dedupe::FILE_NAME, err);
+        }
+        match opts.max_bytes.limit() {
+            Some(limit) => info!(
+                "tasks finished, saved {} of {} budget",
+                human_size(opts.max_bytes.used() as usize, opts.si),
+                human_size(limit as usize, opts.si)
+            ),
+            None => info!("tasks finished"),
+        }
+
+        if let Some(path) = &args.metrics_textfile {
+            if let Err(err) = metrics.write_textfile(Path::new(path), unix_now()) {
+                warn!("cannot write metrics {}", err);
+            }
         }
-        info!("tasks finished");
 
-        if args.interval == 0 {
+        cycles += 1;
+        if let Some(max_cycles) = max_cycles {
+            debug!("{} cycles remaining", max_cycles - cycles);
+            if cycles == max_cycles {
+                let (saved, bytes, failures) = metrics.totals();
+                info!(
+                    "reached cycle limit, {} cycles saved {} files {} with {} failures",
+                    cycles,
+                    saved,
+                    human_size(bytes as usize, opts.si),
+                    failures
+                );
+                break;
+            }
+        }
+        if (once && max_cycles.is_none()) || opts.shutdown.is_requested() {
             break;
         }
     }
@@ -279,43 +1355,857 @@ async fn main() {
     info!("program exited");
 }
 
-#[inline]
-fn check_dir(path: &Path) -> Result<(), std::io::Error> {
-    if path.is_dir() {
+impl Args {
+    /// `-q`, default, `-v`, `-vv`, for dependencies too
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Warn;
+        }
+        match self.verbose + self.debug as u8 {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+
+    /// check what clap can't, e.g. each --custom has its list and dir
+    fn validate(&self) -> Result<(), clap::Error> {
+        let counts = [
+            self.custom.len(),
+            self.custom_list.len(),
+            self.custom_dir.len(),
+        ];
+        if counts.iter().any(|x| *x != counts[0]) {
+            return Err(Args::command().error(
+                ErrorKind::WrongNumberOfValues,
+                format!(
+                    "--custom, --custom-list and --custom-dir must be given the same times, got {}, {} and {}",
+                    counts[0], counts[1], counts[2]
+                ),
+            ));
+        }
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since >= until {
+                return Err(Args::command().error(
+                    ErrorKind::ValueValidation,
+                    "--since must be earlier than --until",
+                ));
+            }
+        }
+        if self.max_age.is_some_and(|x| x.is_zero()) || self.keep_files == Some(0) {
+            return Err(Args::command().error(
+                ErrorKind::ValueValidation,
+                "--max-age and --keep-files must be more than 0, or every file is deleted",
+            ));
+        }
+        if self.max_files == Some(0) {
+            return Err(Args::command().error(
+                ErrorKind::ValueValidation,
+                "--max-files must be more than 0",
+            ));
+        }
+        if !self.custom_name.is_empty() && self.custom_name.len() != counts[0] {
+            return Err(Args::command().error(
+                ErrorKind::WrongNumberOfValues,
+                format!(
+                    "--custom-name must be given for every --custom, got {} names for {} tasks",
+                    self.custom_name.len(),
+                    counts[0]
+                ),
+            ));
+        }
         Ok(())
+    }
+}
+
+/// create tasks of flags and config file
+fn build_tasks(args: &Args) -> Result<Vec<Task>, Box<dyn Error>> {
+    let mut tasks = Vec::new();
+
+    let mode = if args.regex {
+        Mode::Regex
+    } else if args.glob {
+        Mode::Glob
     } else {
-        std::fs::create_dir_all(path)
+        Mode::Contains
+    };
+    let matcher = |patterns: Vec<String>| {
+        Matcher::new(patterns, mode).map_err(|err| format!("invalid pattern\n{}", err))
+    };
+
+    let exclude = matcher(args.exclude.clone())?;
+    let filter = Filter {
+        any: args.contains_any.clone(),
+        all: args.contains_all.clone(),
+    };
+
+    // one task per source, so the list is only fetched once per cycle
+    if !args.sat_img.is_empty() {
+        tasks.push(Task::new_sat(matcher(args.sat_img.clone())?));
     }
+
+    if !args.radar_cloud.is_empty() {
+        tasks.push(Task::new_radar(matcher(args.radar_cloud.clone())?));
+    }
+
+    if !args.radar_rain.is_empty() {
+        tasks.push(Task::new_radar_rain(matcher(args.radar_rain.clone())?));
+    }
+
+    if !args.lightning.is_empty() {
+        tasks.push(Task::new_lightning(matcher(args.lightning.clone())?));
+    }
+
+    if !args.rainfall.is_empty() {
+        tasks.push(Task::new_rainfall(matcher(args.rainfall.clone())?));
+    }
+
+    if !args.typhoon.is_empty() {
+        tasks.push(Task::new_typhoon(matcher(args.typhoon.clone())?));
+    }
+
+    if !args.weather_chart.is_empty() {
+        tasks.push(Task::new_weather_chart(matcher(
+            args.weather_chart.clone(),
+        )?));
+    }
+
+    if !args.surface_chart.is_empty() {
+        tasks.push(Task::new_surface_chart(matcher(
+            args.surface_chart.clone(),
+        )?));
+    }
+
+    let customs = args
+        .custom
+        .iter()
+        .zip(&args.custom_list)
+        .zip(&args.custom_dir);
+    for (i, ((custom, list), dir)) in customs.enumerate() {
+        // keep the old name when there is only one
+        let name = if let Some(name) = args.custom_name.get(i) {
+            name.clone()
+        } else if args.custom.len() == 1 {
+            "custom".to_string()
+        } else {
+            format!("custom_{}", i + 1)
+        };
+        let (list, dir) = task::custom_paths(&args.host, list, dir)
+            .map_err(|err| format!("invalid --custom-list or --custom-dir of {}: {}", name, err))?;
+        let task = Task::new(name, list, dir, matcher(vec![custom.clone()])?);
+        task.hint_empty.store(true, Ordering::Relaxed);
+        tasks.push(task);
+    }
+
+    let save_dirs = [
+        (source::SAT.name, &args.sat_dir),
+        (source::RADAR.name, &args.radar_dir),
+        (source::RADAR_RAIN.name, &args.radar_rain_dir),
+        (source::LIGHTNING.name, &args.lightning_dir),
+        (source::RAINFALL.name, &args.rainfall_dir),
+        (source::TYPHOON.name, &args.typhoon_dir),
+        (source::WEATHER_CHART.name, &args.weather_chart_dir),
+        (source::SURFACE_CHART.name, &args.surface_chart_dir),
+    ];
+    for task in &mut tasks {
+        task.exclude = exclude.clone();
+        task.filter = filter.clone();
+        task.text_contains = args.text_contains.clone();
+        task.save_dir = save_dirs
+            .iter()
+            .find(|(name, _)| *name == task.name)
+            .and_then(|(_, dir)| (*dir).clone());
+    }
+
+    // tasks of config file and --tasks, global filters unless overridden
+    let from_config = |task_config: TaskConfig| -> Result<Task, Box<dyn Error>> {
+        let name = task_config.name.unwrap_or_else(|| {
+            Path::new(&task_config.list)
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or("config")
+                .to_string()
+        });
+        let mut task = Task::new(
+            name,
+            task_config.list,
+            task_config.dir,
+            match task_config.contains {
+                Some(patterns) => matcher(patterns.into())?,
+                None => Matcher::all(),
+            },
+        );
+        task.exclude = match task_config.exclude {
+            Some(patterns) => matcher(patterns.into())?,
+            None => exclude.clone(),
+        };
+        task.filter = Filter {
+            any: task_config
+                .contains_any
+                .map_or_else(|| filter.any.clone(), Into::into),
+            all: task_config
+                .contains_all
+                .map_or_else(|| filter.all.clone(), Into::into),
+        };
+        task.text_contains = match task_config.text_contains {
+            Some(patterns) => patterns.into(),
+            None => args.text_contains.clone(),
+        };
+        if let Some(interval) = task_config.interval.filter(|x| *x < MIN_INTERVAL) {
+            warn!(
+                "interval {} of {} is shorter than {}",
+                humantime::format_duration(interval),
+                task.name,
+                humantime::format_duration(MIN_INTERVAL)
+            );
+        }
+        task.interval = task_config.interval;
+        task.save_dir = task_config.out_dir.map(PathBuf::from);
+        Ok(task)
+    };
+
+    if let Some(path) = &args.config {
+        let config = Config::load(Path::new(path))
+            .and_then(|x| Ok(x.into_tasks()?))
+            .map_err(|err| format!("cannot load config {}: {}", path, err))?;
+
+        for task_config in config {
+            tasks.push(from_config(task_config)?);
+        }
+    }
+
+    if let Some(path) = &args.tasks {
+        let task_configs = config::load_tasks(path)
+            .map_err(|err| format!("cannot load tasks {}: {}", path, err))?;
+
+        for (i, task_config) in task_configs.into_iter().enumerate() {
+            let task =
+                from_config(task_config).map_err(|err| format!("invalid task {}: {}", i, err))?;
+            tasks.push(task);
+        }
+    }
+
+    if args.per_task_dirs {
+        if let Some(task) = tasks.iter().find(|x| !is_safe_filename(&x.name)) {
+            return Err(format!("task name {:?} cannot be used as dir", task.name).into());
+        }
+    }
+
+    Ok(tasks)
+}
+
+fn flush_manifest(opts: &RunOptions) {
+    if let Some(manifest) = &opts.manifest {
+        if let Err(err) = manifest.flush() {
+            warn!("cannot write {} {}", manifest::FILE_NAME, err);
+        }
+    }
+}
+
+fn write_completions(shell: Shell, out: &mut dyn io::Write) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
+fn write_manpage(out: &mut dyn io::Write) -> io::Result<()> {
+    clap_mangen::Man::new(Args::command()).render(out)
 }
 
-#[inline]
-async fn save_stream<T>(dest: &Path, stream: &mut T) -> Result<usize, Box<dyn Error>>
-where
-    T: Stream<Item = reqwest::Result<Bytes>> + std::marker::Unpin,
-{
-    let mut file = File::create(dest).await?;
-    let mut writed = 0;
+/// print matched objects of a list file with their source, or near matches.
+/// with `emit`, also writes the parsed value as js to path, under var name
+fn parse_file(
+    path: &Path,
+    ignore_case: bool,
+    emit: Option<(&Path, &str)>,
+) -> Result<(), Box<dyn Error>> {
+    let source = std::fs::read_to_string(path)?;
+    let (object, spans) = parse_source_spanned(&source)?;
+
+    if let Some((dest, name)) = emit {
+        if !is_js_identifier(name) {
+            return Err(format!("{:?} is not a js variable name", name).into());
+        }
+        std::fs::write(dest, value_to_js(name, &object))?;
+        info!("written {}", dest.to_str().unwrap());
+    }
+
+    let imgs: Vec<(Img, Range<usize>)> = find_objects_spanned(object.clone(), &spans, ignore_case);
+    for (img, range) in &imgs {
+        println!("{} bytes {}..{}", img.img, range.start, range.end);
+        println!("{}", &source[range.clone()]);
+    }
 
-    while let Some(data) = stream.next().await {
-        writed += file.write(data?.chunk()).await?
+    if imgs.is_empty() {
+        for (pointer, keys) in schema_hints_at(&object, "img") {
+            warn!("object at {} has keys {:?}, not [img, text]", pointer, keys);
+            if let Some(range) = spans.get(&pointer) {
+                println!("{}", &source[range.clone()]);
+            }
+        }
     }
 
-    Ok(writed)
+    Ok(())
 }
 
-#[inline]
-fn human_size(size: usize) -> String {
-    let units = ['K', 'M', 'G', 'T'];
-    let mut unit = ' ';
-    let mut fsize = size as f64;
-    for u in units {
-        if fsize / 1024.0 < 1.0 {
-            break;
+/// download files by name, returns count of failed files. nothing is
+/// downloaded if any name is not a plain file name
+async fn fetch_files(
+    client: &CwaClient,
+    task: &Task,
+    names: &[String],
+    out_dir: &Path,
+    opts: &RunOptions,
+) -> Result<usize, Box<dyn Error>> {
+    if let Some(name) = names.iter().find(|x| !is_safe_filename(x)) {
+        return Err(format!("invalid file name {:?}", name).into());
+    }
+
+    let mut failed = 0;
+    for name in names {
+        let img = Img {
+            img: name.clone(),
+            text: String::new(),
+            ..Default::default()
+        };
+        match task.fetch(client, &img, out_dir, opts).await {
+            Ok(Fetched::Saved(_)) => {}
+            Ok(Fetched::Exists) => info!("{} already exists", name),
+            Ok(Fetched::Filtered) => info!("{} smaller than min dimensions", name),
+            Ok(Fetched::TooLarge | Fetched::Rejected | Fetched::Invalid) => failed += 1,
+            Err(err) => {
+                error!("{}: {}", name, err);
+                failed += 1;
+            }
         }
+    }
+
+    Ok(failed)
+}
+
+/// plain file name, no path component
+fn is_safe_filename(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cwa_images::source::OBSERVE_SAT_LIST;
+    use cwa_images::task::{in_window, keep_newest};
+
+    #[test]
+    fn test_safe_filename() {
+        assert!(is_safe_filename("CV1_3600_202405011230.png"));
+        assert!(is_safe_filename("衛星.jpg"));
+        assert!(!is_safe_filename(""));
+        assert!(!is_safe_filename(".."));
+        assert!(!is_safe_filename("../radar/CV1.png"));
+        assert!(!is_safe_filename("/etc/passwd"));
+        assert!(!is_safe_filename("a\\b.png"));
+        assert!(!is_safe_filename("a\nb.png"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_files_invalid_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = Task::from_name("radar", Matcher::Contains(Vec::new())).unwrap();
+        let names = [
+            "CV1_3600_202405011230.png".to_string(),
+            "../x.png".to_string(),
+        ];
+        let err = fetch_files(
+            &CwaClient::default(),
+            &task,
+            &names,
+            dir.path(),
+            &RunOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!("invalid file name \"../x.png\"", err.to_string());
+        // checked before any download
+        assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn test_save_dirs() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--sat-img",
+            "LCC_TRGB",
+            "--sat-dir",
+            "/mnt/archive/sat",
+            "--radar-cloud",
+            "CV1_3600",
+            "--per-task-dirs",
+            "out",
+        ]);
+        let tasks = build_tasks(&args).unwrap();
+        let opts = RunOptions {
+            per_task_dirs: true,
+            ..Default::default()
+        };
+        let base = Path::new(&args.dir);
+        assert_eq!(Path::new("/mnt/archive/sat"), tasks[0].out_dir(base, &opts));
+        assert_eq!(Path::new("out/radar"), tasks[1].out_dir(base, &opts));
+
+        assert!(Args::try_parse_from(["cwa_images", "--radar-dir", "/srv/radar"]).is_err());
+    }
+
+    #[test]
+    fn test_unsafe_task_name() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--per-task-dirs",
+            "--custom",
+            "L_",
+            "--custom-list",
+            "/Data/js/obs_img/Observe_lightning.js",
+            "--custom-dir",
+            "/Data/lightning/",
+            "--custom-name",
+            "../lightning",
+        ]);
+        assert!(build_tasks(&args).is_err());
+    }
+
+    #[test]
+    fn test_repeated_flags() {
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "--sat-img", "VIS"]);
+        let tasks = build_tasks(&args).unwrap();
+
+        // single task per source, matching any of the patterns, so the list
+        // is fetched once and files go to one dir of the source
+        assert_eq!(1, tasks.len());
+        assert_eq!(OBSERVE_SAT_LIST, tasks[0].list);
+        assert!(tasks[0].contains.is_match("LCC_B13_2750.jpg"));
+        assert!(tasks[0].contains.is_match("LCC_VIS_2750.jpg"));
+        assert!(!tasks[0].contains.is_match("LCC_TRGB_2750.jpg"));
+
+        let args = Args::parse_from(["cwa_images"]);
+        assert!(build_tasks(&args).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_custom() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--custom",
+            "L_",
+            "--custom-list",
+            "/Data/js/obs_img/Observe_lightning.js",
+            "--custom-dir",
+            "Data/lightning/",
+        ]);
+        let err = build_tasks(&args).unwrap_err().to_string();
+        assert!(
+            err.contains("dir \"Data/lightning/\" must start with /"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_multiple_custom() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--sat-img",
+            "LCC_TRGB",
+            "--custom",
+            "L_",
+            "--custom-list",
+            "/Data/js/obs_img/Observe_lightning.js",
+            "--custom-dir",
+            "/Data/lightning/",
+            "--custom",
+            "UVI",
+            "--custom-list",
+            "/Data/js/obs_img/Observe_uvi.js",
+            "--custom-dir",
+            "/Data/uvi",
+        ]);
+        args.validate().unwrap();
+        let tasks = build_tasks(&args).unwrap();
+
+        assert_eq!(3, tasks.len());
+        assert_eq!("sat", tasks[0].name);
+        assert_eq!("custom_1", tasks[1].name);
+        assert_eq!("/Data/js/obs_img/Observe_lightning.js", tasks[1].list);
+        assert_eq!("/Data/lightning/", tasks[1].dir);
+        assert!(tasks[1].contains.is_match("L_202407241200.jpg"));
+        assert_eq!("custom_2", tasks[2].name);
+        assert_eq!("/Data/js/obs_img/Observe_uvi.js", tasks[2].list);
+        assert_eq!("/Data/uvi/", tasks[2].dir);
+        assert!(!tasks[2].contains.is_match("L_202407241200.jpg"));
+
+        let args = Args::parse_from([
+            "cwa_images",
+            "--custom",
+            "L_",
+            "--custom",
+            "UVI",
+            "--custom-list",
+            "/Data/js/obs_img/Observe_lightning.js",
+            "--custom-dir",
+            "/Data/lightning/",
+        ]);
+        let err = args.validate().unwrap_err();
+        assert_eq!(ErrorKind::WrongNumberOfValues, err.kind());
+    }
+
+    #[test]
+    fn test_exclude() {
+        let args = Args::parse_from(["cwa_images", "--radar-cloud", "CV1_", "--exclude", "_TW_"]);
+        let tasks = build_tasks(&args).unwrap();
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            text: String::new(),
+            ..Default::default()
+        };
+
+        assert!(tasks[0].is_match(&img("CV1_3600_202407241200.png")));
+        assert!(!tasks[0].is_match(&img("CV1_TW_3600_202407241200.png")));
+        assert!(!tasks[0].is_match(&img("RCLY_3600_202407241200.png")));
+
+        // exclude follows --regex
+        let args = Args::parse_from([
+            "cwa_images",
+            "--regex",
+            "--radar-cloud",
+            "^CV1_",
+            "--exclude",
+            "_TW_\\d+",
+        ]);
+        let tasks = build_tasks(&args).unwrap();
+        assert!(tasks[0].is_match(&img("CV1_3600_202407241200.png")));
+        assert!(!tasks[0].is_match(&img("CV1_TW_3600_202407241200.png")));
+    }
+
+    #[test]
+    fn test_contains_any_all() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--sat-img",
+            "LCC_",
+            "--contains-any",
+            "_IR1_",
+            "--contains-any",
+            "_VIS_",
+            "--contains-all",
+            "_CR_",
+            "--contains-all",
+            "2750",
+        ]);
+        let tasks = build_tasks(&args).unwrap();
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            ..Default::default()
+        };
+
+        assert!(tasks[0].is_match(&img("LCC_IR1_CR_2750-2024-07-24-12-00.jpg")));
+        assert!(tasks[0].is_match(&img("LCC_VIS_CR_2750-2024-07-24-12-00.jpg")));
+        assert!(!tasks[0].is_match(&img("LCC_IR1_CR_1000-2024-07-24-12-00.jpg")));
+        assert!(!tasks[0].is_match(&img("LCC_TRGB_CR_2750-2024-07-24-12-00.jpg")));
+        assert!(!tasks[0].is_match(&img("s1p_IR1_CR_2750-2024-07-24-12-00.jpg")));
+    }
+
+    #[test]
+    fn test_text_contains() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--glob",
+            "--sat-img",
+            "",
+            "--text-contains",
+            "彩色",
+            "--text-contains",
+            "真實色",
+        ]);
+        let tasks = build_tasks(&args).unwrap();
+        let img = |name: &str, text: &str| Img {
+            img: name.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        };
+
+        let task = &tasks[0];
+        let color = img("LCC_IR1_CR_2750-2024-07-24-12-00.jpg", "紅外線彩色");
+        assert!(task.is_match(&color) && task.is_text_match(&color));
+        let gray = img("LCC_IR1_BW_2750-2024-07-24-12-00.jpg", "紅外線黑白");
+        assert!(!task.is_text_match(&gray));
+        assert!(task.is_text_match(&img("LCC_TRGB_2750.jpg", "真實色")));
+    }
+
+    #[test]
+    fn test_tasks_json() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let json = r#"[{"name": "uvi", "list": "/Data/js/obs_img/Observe_uvi.js", "dir": "/Data/uvi/", "contains": "UVI"}]"#;
+        io::Write::write_all(&mut file, json.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap();
+
+        // merged with tasks of flags
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "--tasks", path]);
+        let tasks = build_tasks(&args).unwrap();
+        assert_eq!(2, tasks.len());
+        assert_eq!("uvi", tasks[1].name);
+        assert_eq!("/Data/uvi/", tasks[1].dir);
+        assert!(tasks[1].contains.is_match("UVI_202407241200.png"));
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let json =
+            r#"[{"list": "/a.js", "dir": "/"}, {"list": "/b.js", "dir": "/", "contains": "("}]"#;
+        io::Write::write_all(&mut file, json.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap();
+        let args = Args::parse_from(["cwa_images", "--regex", "--tasks", path]);
+        let err = build_tasks(&args).unwrap_err().to_string();
+        assert!(
+            err.starts_with("invalid task 1: invalid pattern"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_log_level() {
+        let level = |args: &[&str]| {
+            let args = [&["cwa_images"], args].concat();
+            Args::parse_from(args).log_level()
+        };
+        assert_eq!(LevelFilter::Info, level(&[]));
+        assert_eq!(LevelFilter::Debug, level(&["-v"]));
+        assert_eq!(LevelFilter::Trace, level(&["-vv"]));
+        assert_eq!(LevelFilter::Warn, level(&["--quiet"]));
+        // old flag still works
+        assert_eq!(LevelFilter::Debug, level(&["--debug"]));
+        assert_eq!(LevelFilter::Trace, level(&["-d", "-v"]));
+        assert!(Args::try_parse_from(["cwa_images", "-q", "-v"]).is_err());
+    }
 
-        fsize /= 1024.0;
-        unit = u;
+    #[test]
+    fn test_keep_newest() {
+        let frames: Vec<usize> = (0..10).collect();
+
+        let mut first = frames.clone();
+        assert_eq!(7, keep_newest(&mut first, 3));
+        assert_eq!(vec![7, 8, 9], first);
+
+        let mut few = vec![1, 2];
+        assert_eq!(0, keep_newest(&mut few, 3));
+        assert_eq!(vec![1, 2], few);
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "list"]);
+        assert!(matches!(args.command, Some(Command::List)));
+        assert_eq!(1, build_tasks(&args).unwrap().len());
+
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "out", "fetch"]);
+        assert!(matches!(args.command, Some(Command::Fetch)));
+        assert_eq!("out", args.dir);
+
+        let args = Args::parse_from(["cwa_images", "fetch"]);
+        assert!(matches!(args.command, Some(Command::Fetch)));
+        assert_eq!("images", args.dir);
+
+        let args = Args::parse_from([
+            "cwa_images",
+            "--per-task-dirs",
+            "--radar-cloud",
+            "CV1",
+            "verify",
+        ]);
+        assert!(matches!(args.command, Some(Command::Verify)));
+
+        let args = Args::parse_from([
+            "cwa_images",
+            "--trash-dir",
+            "trash",
+            "restore",
+            "--dir",
+            "images/radar",
+            "CV1_3600_202405011230.png",
+        ]);
+        let Some(Command::Restore { dir, name }) = args.command else {
+            panic!("{:?}", args.command);
+        };
+        assert_eq!(Some("images/radar"), dir.as_deref());
+        assert_eq!("CV1_3600_202405011230.png", name);
+
+        // options also after the subcommand
+        let args = Args::parse_from(["cwa_images", "prune", "--max-age", "7d"]);
+        assert!(matches!(args.command, Some(Command::Prune)));
+        assert_eq!(Some(Duration::from_secs(7 * 86400)), args.max_age);
+        let args = Args::parse_from(["cwa_images", "out", "fetch", "--sat-img", "B13"]);
+        assert!(matches!(args.command, Some(Command::Fetch)));
+        assert_eq!(vec!["B13"], args.sat_img);
+        assert_eq!("out", args.dir);
+        let args = Args::parse_from(["cwa_images", "verify", "--per-task-dirs"]);
+        assert!(args.per_task_dirs);
+
+        // flags only, as before subcommands
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "out"]);
+        assert!(args.command.is_none());
+        assert_eq!("out", args.dir);
+    }
+
+    #[test]
+    fn test_interval() {
+        let args = Args::parse_from(["cwa_images", "-i", "600"]);
+        assert_eq!(Duration::from_secs(600), args.interval);
+        let args = Args::parse_from(["cwa_images", "--interval", "1h30m"]);
+        assert_eq!(Duration::from_secs(5400), args.interval);
+        let args = Args::parse_from(["cwa_images"]);
+        assert!(args.interval.is_zero());
+        assert!(Args::try_parse_from(["cwa_images", "-i", "0s"]).is_err());
+
+        let args = Args::parse_from(["cwa_images", "--cron", "*/10 * * * *"]);
+        assert!(args.cron.is_some());
+        assert!(Args::try_parse_from(["cwa_images", "--cron", "*/10 * * *"]).is_err());
+        assert!(Args::try_parse_from(["cwa_images", "--cron", "* * * * *", "-i", "10m"]).is_err());
+
+        assert!(args.jitter.is_zero());
+        let args = Args::parse_from(["cwa_images", "--cron", "*/10 * * * *", "--jitter", "30s"]);
+        assert_eq!(Duration::from_secs(30), args.jitter);
+
+        assert_eq!(Duration::from_secs(3600), args.freshness_window);
+        let args = Args::parse_from(["cwa_images", "--freshness-window", "15m"]);
+        assert_eq!(Duration::from_secs(900), args.freshness_window);
+
+        let args = Args::parse_from(["cwa_images", "-i", "10m", "--cycles", "3"]);
+        assert_eq!(Some(3), args.cycles);
+        args.validate().unwrap();
+        let args = Args::parse_from(["cwa_images", "-i", "5m", "--max-cycles", "12"]);
+        assert_eq!(Some(12), args.cycles);
     }
 
-    format!("{:.2}{}B", fsize, unit)
+    #[test]
+    fn test_headers() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--user-agent",
+            "Mozilla/5.0",
+            "--header",
+            "Referer: https://www.cwa.gov.tw/",
+            "--header",
+            "X-Token: abc",
+        ]);
+        assert_eq!("Mozilla/5.0", args.user_agent.unwrap());
+        assert_eq!(2, args.header.len());
+        assert_eq!("referer", args.header[0].0.as_str());
+
+        assert!(Args::try_parse_from(["cwa_images", "--header", "Referer"]).is_err());
+        assert!(Args::try_parse_from(["cwa_images", "--header", "Bad Name: x"]).is_err());
+    }
+
+    #[test]
+    fn test_retention() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--max-age",
+            "7d",
+            "--keep-files",
+            "100",
+            "prune",
+        ]);
+        assert_eq!(Some(Duration::from_secs(7 * 86400)), args.max_age);
+        assert_eq!(Some(100), args.keep_files);
+        assert!(matches!(args.command, Some(Command::Prune)));
+        assert!(args.trash_dir.is_none());
+
+        let args = Args::parse_from([
+            "cwa_images",
+            "--trash-dir",
+            "trash",
+            "--trash-max-age",
+            "30d",
+        ]);
+        assert_eq!(Some(PathBuf::from("trash")), args.trash_dir);
+        assert_eq!(Some(Duration::from_secs(30 * 86400)), args.trash_max_age);
+        assert!(Args::try_parse_from(["cwa_images", "--trash-max-age", "30d"]).is_err());
+
+        let args = Args::parse_from(["cwa_images", "--keep-files", "0"]);
+        assert!(args.validate().is_err());
+        let args = Args::parse_from(["cwa_images", "--max-age", "0"]);
+        assert!(args.validate().is_err());
+        let args = Args::parse_from(["cwa_images", "--max-files", "0"]);
+        assert!(args.validate().is_err());
+    }
+
+    #[test]
+    fn test_limit_alias() {
+        let args = Args::parse_from(["cwa_images", "--sat-img", "B13", "--limit", "3"]);
+        assert_eq!(Some(3), args.latest);
+    }
+
+    #[test]
+    fn test_in_window() {
+        let args = Args::parse_from([
+            "cwa_images",
+            "--since",
+            "2024-07-24",
+            "--until",
+            "2024-07-24T12:00",
+        ]);
+        args.validate().unwrap();
+        let window = Window {
+            since: args.since,
+            until: args.until,
+        };
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            ..Default::default()
+        };
+
+        assert!(in_window(&img("CV1_3600_202407240000.png"), &window));
+        assert!(in_window(
+            &img("LCC_IR1_CR_2750-2024-07-24-11-50.jpg"),
+            &window
+        ));
+        assert!(!in_window(&img("CV1_3600_202407241200.png"), &window));
+        assert!(!in_window(&img("CV1_3600_202407232350.png"), &window));
+        // no time
+        assert!(!in_window(&img("CV1_3600.png"), &window));
+        assert!(in_window(&img("CV1_3600.png"), &Window::default()));
+
+        let args = Args::parse_from([
+            "cwa_images",
+            "--since",
+            "2024-07-25",
+            "--until",
+            "2024-07-24",
+        ]);
+        assert_eq!(
+            ErrorKind::ValueValidation,
+            args.validate().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut out = Vec::new();
+            write_completions(shell, &mut out);
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("--custom-list"), "{}", shell);
+        }
+
+        let mut out = Vec::new();
+        write_manpage(&mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+        assert!(page.starts_with(".ie"));
+        assert!(page.contains("custom\\-list"));
+    }
 }
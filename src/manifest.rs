@@ -0,0 +1,288 @@
+//! `manifest.json` of saved and trashed files, for downstream processing
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const FILE_NAME: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// path in the list
+    pub img: String,
+    pub url: String,
+    /// saved file, relative to the manifest's dir
+    pub file: String,
+    pub size: u64,
+    /// hex of content, none in manifests of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+    /// ISO 8601 in UTC
+    pub time: String,
+}
+
+impl Entry {
+    pub fn new(img: &str, url: &str, file: String, data: &[u8]) -> Self {
+        Self {
+            img: img.to_string(),
+            url: url.to_string(),
+            file,
+            size: data.len() as u64,
+            blake3: Some(blake3::hash(data).to_hex().to_string()),
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// image moved to `--trash-dir` by retention
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trashed {
+    /// former path, relative to the manifest's dir
+    pub file: String,
+    pub trash_path: String,
+    /// ISO 8601 in UTC
+    pub time: String,
+}
+
+/// item of `manifest.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Record {
+    Saved(Entry),
+    Trashed(Trashed),
+}
+
+/// records of a cycle keyed by dir, until written by `flush`
+#[derive(Debug, Default)]
+pub struct Manifest(Mutex<BTreeMap<PathBuf, Vec<Record>>>);
+
+impl Manifest {
+    /// `dest` saved under `dir` with content `data`
+    pub fn record(&self, dir: &Path, dest: &Path, img: &str, url: &str, data: &[u8]) {
+        let entry = Entry::new(img, url, relative(dir, dest), data);
+        self.push(dir, Record::Saved(entry));
+    }
+
+    /// `path` of `dir` moved to `trash_path`
+    pub fn trashed(&self, dir: &Path, path: &Path, trash_path: &Path) {
+        let trashed = Trashed {
+            file: relative(dir, path),
+            trash_path: trash_path.to_string_lossy().to_string(),
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        self.push(dir, Record::Trashed(trashed));
+    }
+
+    fn push(&self, dir: &Path, record: Record) {
+        let mut pending = self.0.lock().unwrap();
+        pending.entry(dir.to_path_buf()).or_default().push(record);
+    }
+
+    /// append recorded entries to `manifest.json` of each dir. entries of
+    /// files deleted since, e.g. by retention, are dropped, and an
+    /// overwritten file keeps its newest entry. moves to the trash are kept
+    pub fn flush(&self) -> io::Result<()> {
+        let pending = std::mem::take(&mut *self.0.lock().unwrap());
+        for (dir, records) in pending {
+            let mut all = read_records(&dir)?;
+            all.extend(records);
+            let mut seen = HashSet::new();
+            all.reverse();
+            all.retain(|x| match x {
+                Record::Saved(x) => seen.insert(x.file.clone()) && dir.join(&x.file).is_file(),
+                Record::Trashed(_) => true,
+            });
+            all.reverse();
+            write(&dir, &all)?;
+        }
+        Ok(())
+    }
+}
+
+/// `path` relative to `dir` with `/`
+fn relative(dir: &Path, path: &Path) -> String {
+    let file = path.strip_prefix(dir).unwrap_or(path);
+    file.to_string_lossy().replace('\\', "/")
+}
+
+/// saved entries of `manifest.json` in `dir`, empty if none
+pub fn read(dir: &Path) -> io::Result<Vec<Entry>> {
+    let records = read_records(dir)?.into_iter();
+    Ok(records
+        .filter_map(|x| match x {
+            Record::Saved(x) => Some(x),
+            Record::Trashed(_) => None,
+        })
+        .collect())
+}
+
+/// all records of `manifest.json` in `dir`, empty if none
+pub fn read_records(dir: &Path) -> io::Result<Vec<Record>> {
+    match std::fs::read(dir.join(FILE_NAME)) {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// replace by rename, readers never see a partial file
+fn write(dir: &Path, entries: &[Record]) -> io::Result<()> {
+    let path = dir.join(FILE_NAME);
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(entries)?)?;
+    std::fs::rename(&tmp, &path)
+}
+
+/// problem of a recorded file
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    Missing,
+    Size {
+        actual: u64,
+    },
+    /// same size but other content
+    Checksum {
+        actual: String,
+    },
+}
+
+/// recorded files of `dir` that are missing or changed in size or content,
+/// content is checked only for entries with a hash
+pub fn verify(dir: &Path) -> io::Result<Vec<(Entry, Mismatch)>> {
+    let mut mismatches = Vec::new();
+    for entry in read(dir)? {
+        match std::fs::read(dir.join(&entry.file)) {
+            Ok(data) if data.len() as u64 != entry.size => {
+                let actual = data.len() as u64;
+                mismatches.push((entry, Mismatch::Size { actual }));
+            }
+            Ok(data) => {
+                let actual = blake3::hash(&data).to_hex().to_string();
+                if entry.blake3.as_ref().is_some_and(|x| *x != actual) {
+                    mismatches.push((entry, Mismatch::Checksum { actual }));
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                mismatches.push((entry, Mismatch::Missing));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = dir.path().join("2024/07/24");
+        std::fs::create_dir_all(&day).unwrap();
+        let dest = day.join("CV1_3600_202407241200.png");
+        std::fs::write(&dest, b"png").unwrap();
+
+        let manifest = Manifest::default();
+        let url = "https://www.cwa.gov.tw/Data/radar/CV1_3600_202407241200.png";
+        manifest.record(dir.path(), &dest, "CV1_3600_202407241200.png", url, b"png");
+        manifest.flush().unwrap();
+        // appended by the next cycle
+        let other = dir.path().join("CV1_3600.png");
+        std::fs::write(&other, b"").unwrap();
+        manifest.record(dir.path(), &other, "CV1_3600.png", url, b"");
+        manifest.flush().unwrap();
+
+        let entries = read(dir.path()).unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!("2024/07/24/CV1_3600_202407241200.png", entries[0].file);
+        assert_eq!(url, entries[0].url);
+        assert_eq!(3, entries[0].size);
+        assert_eq!(
+            Some(blake3::hash(b"png").to_hex().to_string()),
+            entries[0].blake3
+        );
+        assert!(entries[0].time.ends_with('Z'));
+        assert!(!dir.path().join("manifest.json.tmp").exists());
+
+        // deleted file is dropped on the next flush, overwritten file keeps
+        // the newest entry
+        std::fs::remove_file(&other).unwrap();
+        manifest.record(dir.path(), &dest, "CV1_3600_202407241200.png", url, b"png2");
+        manifest.flush().unwrap();
+        let entries = read(dir.path()).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(4, entries[0].size);
+    }
+
+    #[test]
+    fn test_trashed() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("CV1_3600_202407241200.png");
+        std::fs::write(&dest, b"png").unwrap();
+        let manifest = Manifest::default();
+        manifest.record(
+            dir.path(),
+            &dest,
+            "CV1_3600_202407241200.png",
+            "url",
+            b"png",
+        );
+        manifest.flush().unwrap();
+
+        let trash_path = Path::new("trash/2024-07-25/CV1_3600_202407241200.png");
+        std::fs::remove_file(&dest).unwrap();
+        manifest.trashed(dir.path(), &dest, trash_path);
+        manifest.flush().unwrap();
+
+        // saved entry of the moved file is dropped, the move is kept
+        assert!(read(dir.path()).unwrap().is_empty());
+        let records = read_records(dir.path()).unwrap();
+        assert_eq!(1, records.len());
+        let Record::Trashed(trashed) = &records[0] else {
+            panic!("{:?}", records[0]);
+        };
+        assert_eq!("CV1_3600_202407241200.png", trashed.file);
+        assert_eq!(trash_path.to_str().unwrap(), trashed.trash_path);
+        assert!(verify(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(verify(dir.path()).unwrap().is_empty());
+
+        let manifest = Manifest::default();
+        for name in ["a.png", "b.png", "c.png", "d.png"] {
+            let dest = dir.path().join(name);
+            std::fs::write(&dest, b"png").unwrap();
+            manifest.record(dir.path(), &dest, name, name, b"png");
+        }
+        manifest.flush().unwrap();
+        std::fs::write(dir.path().join("b.png"), b"").unwrap();
+        std::fs::remove_file(dir.path().join("c.png")).unwrap();
+        // corrupted in place
+        std::fs::write(dir.path().join("d.png"), b"PNG").unwrap();
+
+        let mismatches = verify(dir.path()).unwrap();
+        assert_eq!(3, mismatches.len());
+        assert_eq!(Mismatch::Size { actual: 0 }, mismatches[0].1);
+        assert_eq!("c.png", mismatches[1].0.file);
+        assert_eq!(Mismatch::Missing, mismatches[1].1);
+        assert_eq!("d.png", mismatches[2].0.file);
+        let actual = blake3::hash(b"PNG").to_hex().to_string();
+        assert_eq!(Mismatch::Checksum { actual }, mismatches[2].1);
+
+        // entries without hash are checked by size only
+        let mut entries = read(dir.path()).unwrap();
+        entries.retain(|x| x.file == "d.png");
+        entries[0].blake3 = None;
+        let records: Vec<Record> = entries.into_iter().map(Record::Saved).collect();
+        write(dir.path(), &records).unwrap();
+        assert!(verify(dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! write-complete markers for directory watchers
+use clap::ValueEnum;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// journal of completed file names, truncated every cycle
+pub const JOURNAL: &str = "COMPLETED";
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Marker {
+    /// zero-byte `<name>.done` next to the image
+    Done,
+    /// append name to `COMPLETED` in the dir
+    Journal,
+}
+
+/// `<dest>.done`
+pub fn done_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(dest.as_os_str());
+    name.push(".done");
+    PathBuf::from(name)
+}
+
+impl Marker {
+    /// flush image to disk, then mark it complete
+    pub async fn mark(&self, dest: &Path) -> io::Result<()> {
+        File::open(dest).await?.sync_all().await?;
+
+        match self {
+            Self::Done => {
+                let marker = File::create(done_path(dest)).await?;
+                marker.sync_all().await
+            }
+            Self::Journal => {
+                let dir = dest.parent().unwrap_or(Path::new("."));
+                let name = dest.file_name().unwrap_or_default().to_string_lossy();
+                let mut journal = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join(JOURNAL))
+                    .await?;
+                journal.write_all(format!("{}\n", name).as_bytes()).await?;
+                journal.sync_all().await
+            }
+        }
+    }
+
+    /// check marker of existing image, for images saved before crash
+    pub async fn is_marked(&self, dest: &Path) -> bool {
+        match self {
+            Self::Done => done_path(dest).is_file(),
+            // journal is per cycle, can't tell
+            Self::Journal => true,
+        }
+    }
+
+    /// start a new cycle, dirs not created yet are skipped
+    pub async fn reset(&self, dir: &Path) -> io::Result<()> {
+        if *self == Self::Journal && dir.is_dir() {
+            File::create(dir.join(JOURNAL)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{done_path, Marker, JOURNAL};
+    use crate::task::save_file;
+
+    #[tokio::test]
+    async fn test_done_after_durable() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("CV1_3600_202407241200.png");
+        let data = vec![7u8; 64 * 1024];
+
+        save_file(&dest, &mut data.as_slice()).await.unwrap();
+        assert!(!done_path(&dest).exists());
+
+        Marker::Done.mark(&dest).await.unwrap();
+        let marker = done_path(&dest);
+        assert!(marker.is_file());
+        assert_eq!(0, std::fs::metadata(&marker).unwrap().len());
+        // marker exists only when the image is complete
+        assert_eq!(data, std::fs::read(&dest).unwrap());
+        assert!(Marker::Done.is_marked(&dest).await);
+    }
+
+    #[tokio::test]
+    async fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join(JOURNAL);
+
+        for name in ["a.png", "b.png"] {
+            let dest = dir.path().join(name);
+            save_file(&dest, &mut b"png".as_slice()).await.unwrap();
+            Marker::Journal.mark(&dest).await.unwrap();
+        }
+        assert_eq!("a.png\nb.png\n", std::fs::read_to_string(&journal).unwrap());
+
+        // marking a missing image fails without touching the journal
+        assert!(Marker::Journal
+            .mark(&dir.path().join("c.png"))
+            .await
+            .is_err());
+
+        Marker::Journal.reset(dir.path()).await.unwrap();
+        assert_eq!("", std::fs::read_to_string(&journal).unwrap());
+    }
+}
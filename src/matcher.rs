@@ -0,0 +1,218 @@
+//! image name matching
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::error::Error;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Contains,
+    Regex,
+    Glob,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// plain substring, match any
+    Contains(Vec<String>),
+    /// regular expression, match any
+    Regex(Vec<Regex>),
+    /// shell wildcard against file name only
+    Glob(GlobSet),
+}
+
+impl Matcher {
+    pub fn new(patterns: Vec<String>, mode: Mode) -> Result<Self, Box<dyn Error>> {
+        match mode {
+            Mode::Contains => Ok(Self::Contains(patterns)),
+            Mode::Regex => {
+                let regexes = patterns
+                    .iter()
+                    .map(|x| Regex::new(x))
+                    .collect::<Result<_, _>>()?;
+                Ok(Self::Regex(regexes))
+            }
+            Mode::Glob => {
+                let mut builder = GlobSetBuilder::new();
+                for pattern in &patterns {
+                    // empty matches all as in other modes
+                    let pattern = if pattern.is_empty() { "*" } else { pattern };
+                    builder.add(Glob::new(pattern)?);
+                }
+                Ok(Self::Glob(builder.build()?))
+            }
+        }
+    }
+
+    /// matches every name, in any mode
+    pub fn all() -> Self {
+        Self::Contains(vec![String::new()])
+    }
+
+    /// an empty substring matches every name, e.g. of `all`
+    pub fn matches_all(&self) -> bool {
+        matches!(self, Self::Contains(patterns) if patterns.iter().any(String::is_empty))
+    }
+
+    pub fn is_match(&self, img: &str) -> bool {
+        match self {
+            Self::Contains(patterns) => patterns.iter().any(|x| img.contains(x)),
+            Self::Regex(regexes) => regexes.iter().any(|x| x.is_match(img)),
+            Self::Glob(set) => Path::new(img)
+                .file_name()
+                .is_some_and(|name| set.is_match(name)),
+        }
+    }
+}
+
+/// substring groups of file name on top of task patterns, (any of `any`) and
+/// (all of `all`). empty group is no constraint
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub any: Vec<String>,
+    pub all: Vec<String>,
+}
+
+impl Filter {
+    pub fn is_match(&self, img: &str) -> bool {
+        (self.any.is_empty() || self.any.iter().any(|x| img.contains(x)))
+            && self.all.iter().all(|x| img.contains(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Matcher, Mode};
+    use crate::parser::parse_source;
+
+    fn matcher(patterns: &[&str], mode: Mode) -> Matcher {
+        Matcher::new(patterns.iter().map(|x| x.to_string()).collect(), mode).unwrap()
+    }
+
+    #[test]
+    fn test_contains() {
+        let m = matcher(&["TRGB", "B13"], Mode::Contains);
+        assert!(m.is_match("LCC_TRGB_2750-2024-07-24-12-00.jpg"));
+        assert!(m.is_match("LCC_IR1_B13_2750.jpg"));
+        assert!(!m.is_match("LCC_VIS_Gray_2750.jpg"));
+        // regex syntax is literal in substring mode
+        assert!(!matcher(&["RCLY_(3600|0900)"], Mode::Contains).is_match("RCLY_3600.png"));
+
+        assert!(!m.matches_all());
+        assert!(Matcher::all().matches_all());
+        assert!(matcher(&["B13", ""], Mode::Contains).matches_all());
+    }
+
+    #[test]
+    fn test_regex_anchor() {
+        let m = matcher(&[r"^CV1_TW_.*\.png$"], Mode::Regex);
+        assert!(m.is_match("CV1_TW_3600_202407241200.png"));
+        assert!(!m.is_match("/Data/radar/CV1_TW_3600_202407241200.png"));
+        assert!(!m.is_match("CV1_TW_3600_202407241200.png.bak"));
+
+        let m = matcher(&["RCLY_(3600|0900)"], Mode::Regex);
+        assert!(m.is_match("RCLY_0900_202407241200.png"));
+        assert!(!m.is_match("RCLY_1800_202407241200.png"));
+    }
+
+    #[test]
+    fn test_regex_non_ascii() {
+        let m = matcher(&[r"^雷達_\d{4}\.png$"], Mode::Regex);
+        assert!(m.is_match("雷達_1200.png"));
+        assert!(!m.is_match("衛星_1200.png"));
+        assert!(matcher(&["衛星"], Mode::Contains).is_match("衛星_1200.png"));
+    }
+
+    #[test]
+    fn test_regex_invalid() {
+        let err = Matcher::new(vec!["RCLY_(3600".to_string()], Mode::Regex).unwrap_err();
+        assert!(err.to_string().contains("unclosed group"));
+    }
+
+    const LIST: &str = r#"var data = {
+        "sat": [
+            {"img": "s01q_202407241200.jpg", "text": "彩色"},
+            {"img": "s01q_202407241210.jpg", "text": "彩色"},
+            {"img": "s03q_202407241200.jpg", "text": "黑白"},
+            {"img": "/Data/satellite/s01q/s01q_202407241220.png", "text": "with dir"}
+        ]
+    }"#;
+
+    fn matched(patterns: &[&str]) -> Vec<String> {
+        let value = parse_source(LIST).unwrap();
+        let m = matcher(patterns, Mode::Glob);
+        value["sat"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|x| x["img"].as_str())
+            .filter(|x| m.is_match(x))
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_glob() {
+        assert_eq!(
+            vec!["s01q_202407241200.jpg", "s01q_202407241210.jpg"],
+            matched(&["s01q*.jpg"])
+        );
+        // directory of list entry is ignored
+        assert_eq!(
+            vec!["/Data/satellite/s01q/s01q_202407241220.png"],
+            matched(&["s01q*.png"])
+        );
+        assert_eq!(
+            vec!["s01q_202407241210.jpg"],
+            matched(&["s01q_20240724121?.jpg"])
+        );
+        assert_eq!(
+            vec!["s01q_202407241200.jpg", "s03q_202407241200.jpg"],
+            matched(&["s0[13]q_*1200.jpg"])
+        );
+        assert_eq!(vec!["s03q_202407241200.jpg"], matched(&["s0[!1]q_*"]));
+        assert_eq!(4, matched(&[""]).len());
+        assert!(Matcher::new(vec!["s0[1q".to_string()], Mode::Glob).is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let names = [
+            "A_X_Y.png",
+            "A_X.png",
+            "B_Y.png",
+            "B_X_Y.png",
+            "C_X_Y.png",
+            "C.png",
+        ];
+        let matched = |any: &[&str], all: &[&str]| {
+            let filter = Filter {
+                any: any.iter().map(|x| x.to_string()).collect(),
+                all: all.iter().map(|x| x.to_string()).collect(),
+            };
+            names
+                .iter()
+                .filter(|x| filter.is_match(x))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names.to_vec(), matched(&[], &[]));
+        assert_eq!(
+            vec!["A_X_Y.png", "A_X.png", "B_Y.png", "B_X_Y.png"],
+            matched(&["A_", "B_"], &[])
+        );
+        assert_eq!(
+            vec!["A_X_Y.png", "B_X_Y.png", "C_X_Y.png"],
+            matched(&[], &["_X", "_Y"])
+        );
+        // (A or B) and X and Y
+        assert_eq!(
+            vec!["A_X_Y.png", "B_X_Y.png"],
+            matched(&["A_", "B_"], &["_X", "_Y"])
+        );
+        assert!(matched(&["D_"], &[]).is_empty());
+        assert!(matched(&["A_"], &["_Z"]).is_empty());
+    }
+}
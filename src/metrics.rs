@@ -0,0 +1,237 @@
+//! counters of runs, rendered as OpenMetrics text
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// result of a single task run
+#[derive(Debug, Default)]
+pub struct Report {
+    pub saved: u64,
+    pub bytes: u64,
+    pub failures: BTreeMap<&'static str, u64>,
+}
+
+impl Report {
+    pub fn fail(&mut self, class: &'static str) {
+        *self.failures.entry(class).or_default() += 1;
+    }
+}
+
+/// failure class of fetch error
+pub fn failure_class(err: &(dyn Error + 'static)) -> &'static str {
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        http_class(err)
+    } else if let Some(err) = err.downcast_ref::<io::Error>() {
+        // body stream error is wrapped by StreamReader
+        match err
+            .get_ref()
+            .and_then(|x| x.downcast_ref::<reqwest::Error>())
+        {
+            Some(inner) => http_class(inner),
+            None => "io",
+        }
+    } else {
+        "other"
+    }
+}
+
+fn http_class(err: &reqwest::Error) -> &'static str {
+    if err.is_timeout() {
+        "timeout"
+    } else {
+        "http"
+    }
+}
+
+#[derive(Debug, Default)]
+struct TaskMetrics {
+    downloads: u64,
+    bytes: u64,
+    failures: BTreeMap<&'static str, u64>,
+    /// unix timestamp
+    last_success: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    tasks: BTreeMap<String, TaskMetrics>,
+}
+
+impl Metrics {
+    /// record a task run at `now` (unix timestamp), `None` if the task failed
+    pub fn record(&mut self, task: &str, report: Option<&Report>, now: u64) {
+        let metrics = self.tasks.entry(task.to_string()).or_default();
+        match report {
+            Some(report) => {
+                metrics.downloads += report.saved;
+                metrics.bytes += report.bytes;
+                for (class, count) in &report.failures {
+                    *metrics.failures.entry(class).or_default() += count;
+                }
+                metrics.last_success = Some(now);
+            }
+            None => *metrics.failures.entry("task").or_default() += 1,
+        }
+    }
+
+    /// downloads, bytes and failures of all tasks
+    pub fn totals(&self) -> (u64, u64, u64) {
+        self.tasks
+            .values()
+            .fold((0, 0, 0), |(downloads, bytes, failures), x| {
+                (
+                    downloads + x.downloads,
+                    bytes + x.bytes,
+                    failures + x.failures.values().sum::<u64>(),
+                )
+            })
+    }
+
+    pub fn render(&self, now: u64) -> String {
+        let mut out = String::new();
+
+        out.push_str("# TYPE cwa_images_downloads counter\n");
+        for (task, metrics) in &self.tasks {
+            let _ = writeln!(
+                out,
+                "cwa_images_downloads_total{{task=\"{}\"}} {}",
+                escape(task),
+                metrics.downloads
+            );
+        }
+
+        out.push_str("# TYPE cwa_images_download_bytes counter\n");
+        out.push_str("# UNIT cwa_images_download_bytes bytes\n");
+        for (task, metrics) in &self.tasks {
+            let _ = writeln!(
+                out,
+                "cwa_images_download_bytes_total{{task=\"{}\"}} {}",
+                escape(task),
+                metrics.bytes
+            );
+        }
+
+        out.push_str("# TYPE cwa_images_failures counter\n");
+        for (task, metrics) in &self.tasks {
+            for (class, count) in &metrics.failures {
+                let _ = writeln!(
+                    out,
+                    "cwa_images_failures_total{{task=\"{}\",class=\"{}\"}} {}",
+                    escape(task),
+                    class,
+                    count
+                );
+            }
+        }
+
+        out.push_str("# TYPE cwa_images_last_success_timestamp_seconds gauge\n");
+        out.push_str("# UNIT cwa_images_last_success_timestamp_seconds seconds\n");
+        for (task, metrics) in &self.tasks {
+            if let Some(last_success) = metrics.last_success {
+                let _ = writeln!(
+                    out,
+                    "cwa_images_last_success_timestamp_seconds{{task=\"{}\"}} {}",
+                    escape(task),
+                    last_success
+                );
+            }
+        }
+
+        out.push_str("# TYPE cwa_images_staleness_seconds gauge\n");
+        out.push_str("# UNIT cwa_images_staleness_seconds seconds\n");
+        for (task, metrics) in &self.tasks {
+            if let Some(last_success) = metrics.last_success {
+                let _ = writeln!(
+                    out,
+                    "cwa_images_staleness_seconds{{task=\"{}\"}} {}",
+                    escape(task),
+                    now.saturating_sub(last_success)
+                );
+            }
+        }
+
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// rewrite textfile atomically, collector never reads a partial file
+    pub fn write_textfile(&self, path: &Path, now: u64) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.render(now))?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metrics, Report};
+
+    const EXPECT: &str = r#"# TYPE cwa_images_downloads counter
+cwa_images_downloads_total{task="/Data/js/obs_img/Observe_radar.js"} 3
+cwa_images_downloads_total{task="/Data/js/obs_img/Observe_sat.js"} 0
+# TYPE cwa_images_download_bytes counter
+# UNIT cwa_images_download_bytes bytes
+cwa_images_download_bytes_total{task="/Data/js/obs_img/Observe_radar.js"} 3072
+cwa_images_download_bytes_total{task="/Data/js/obs_img/Observe_sat.js"} 0
+# TYPE cwa_images_failures counter
+cwa_images_failures_total{task="/Data/js/obs_img/Observe_radar.js",class="http"} 2
+cwa_images_failures_total{task="/Data/js/obs_img/Observe_sat.js",class="task"} 1
+# TYPE cwa_images_last_success_timestamp_seconds gauge
+# UNIT cwa_images_last_success_timestamp_seconds seconds
+cwa_images_last_success_timestamp_seconds{task="/Data/js/obs_img/Observe_radar.js"} 1000
+# TYPE cwa_images_staleness_seconds gauge
+# UNIT cwa_images_staleness_seconds seconds
+cwa_images_staleness_seconds{task="/Data/js/obs_img/Observe_radar.js"} 600
+# EOF
+"#;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        let radar = "/Data/js/obs_img/Observe_radar.js";
+
+        let mut report = Report {
+            saved: 1,
+            bytes: 1024,
+            ..Default::default()
+        };
+        report.fail("http");
+        metrics.record(radar, Some(&report), 400);
+
+        let mut report = Report {
+            saved: 2,
+            bytes: 2048,
+            ..Default::default()
+        };
+        report.fail("http");
+        metrics.record(radar, Some(&report), 1000);
+        metrics.record("/Data/js/obs_img/Observe_sat.js", None, 1000);
+
+        assert_eq!(EXPECT, metrics.render(1600));
+        assert_eq!((3, 3072, 3), metrics.totals());
+    }
+
+    #[test]
+    fn test_textfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cwa_images.prom");
+        let mut metrics = Metrics::default();
+        metrics.record("a\"b", Some(&Report::default()), 0);
+
+        metrics.write_textfile(&path, 0).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains(r#"cwa_images_downloads_total{task="a\"b"} 0"#));
+        assert!(text.ends_with("# EOF\n"));
+        // temp file renamed
+        assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+}
@@ -0,0 +1,110 @@
+//! minimal http server for tests
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// lowercase header name
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// end of body is the end of connection otherwise
+    pub content_length: bool,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            content_length: true,
+        }
+    }
+
+    pub fn without_length(mut self) -> Self {
+        self.content_length = false;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// serve on a random local port, one response per connection
+pub async fn serve<F>(handler: F) -> SocketAddr
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                break;
+            };
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0; 1024];
+                while !buf.windows(4).any(|x| x == b"\r\n\r\n") {
+                    match stream.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    }
+                }
+
+                let head = String::from_utf8_lossy(&buf);
+                let mut lines = head.lines();
+                let mut start = lines.next().unwrap_or_default().split(' ');
+                let method = start.next().unwrap_or_default().to_string();
+                let path = start.next().unwrap_or_default().to_string();
+                let headers = lines
+                    .filter_map(|x| x.split_once(':'))
+                    .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+                    .collect();
+                let request = Request {
+                    method,
+                    path,
+                    headers,
+                };
+
+                let response = handler(&request);
+                let mut out = format!("HTTP/1.1 {} MOCK\r\n", response.status);
+                let has_length = response
+                    .headers
+                    .iter()
+                    .any(|(k, _)| k.eq_ignore_ascii_case("content-length"));
+                if response.content_length && !has_length {
+                    out.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+                }
+                for (name, value) in &response.headers {
+                    out.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                out.push_str("Connection: close\r\n\r\n");
+
+                let _ = stream.write_all(out.as_bytes()).await;
+                if request.method != "HEAD" {
+                    let _ = stream.write_all(&response.body).await;
+                }
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+
+    addr
+}
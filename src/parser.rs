@@ -1,10 +1,11 @@
 //! imcomplete object parser
 use serde::de::DeserializeOwned;
-use serde_json::Value;
-use std::{error::Error, fmt::Display};
-use swc_common::{input::StringInput, source_map::SmallPos, BytePos};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, error::Error, fmt::Display, ops::Range};
+use swc_common::{input::StringInput, source_map::SmallPos, BytePos, Span, Spanned};
 use swc_ecma_ast::{
-    Decl, Expr, KeyValueProp, Lit, Prop, PropName, PropOrSpread, Script, Stmt, UnaryExpr, UnaryOp,
+    BinExpr, BinaryOp, Decl, Expr, KeyValueProp, Lit, Pat, Prop, PropName, PropOrSpread, Script,
+    Stmt, Tpl, UnaryExpr, UnaryOp,
 };
 use swc_ecma_parser::{error::Error as SWCParseError, Parser};
 
@@ -12,67 +13,237 @@ use swc_ecma_parser::{error::Error as SWCParseError, Parser};
 pub struct ParseError {
     kind: String,
     message: String,
+    /// 1-based, 0 if the error has no position
+    pub line: usize,
+    /// 1-based in characters, 0 if the error has no position
+    pub column: usize,
 }
 
-impl From<SWCParseError> for ParseError {
-    fn from(value: SWCParseError) -> Self {
+impl ParseError {
+    fn from_swc(err: SWCParseError, source: &str) -> Self {
+        let (line, column) = line_column(source, err.span().lo.to_usize());
         Self {
             kind: "parse error".into(),
-            message: value.kind().msg().to_string(),
+            message: err.kind().msg().to_string(),
+            line,
+            column,
         }
     }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {}", self.kind, self.message)
+        if self.line == 0 {
+            write!(f, "{}: {}", self.kind, self.message)
+        } else {
+            write!(
+                f,
+                "{} at line {}, column {}: {}",
+                self.kind, self.line, self.column, self.message
+            )
+        }
+    }
+}
+
+/// 1-based line and column of byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut end = offset.min(source.len());
+    while !source.is_char_boundary(end) {
+        end -= 1;
     }
+    let before = &source[..end];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
 }
 
 impl Error for ParseError {}
 
+/// byte ranges in source of parsed values, keyed by JSON pointer
+pub type Spans = HashMap<String, Range<usize>>;
+
+/// span side table, filled while building the value
+#[derive(Default)]
+struct SpanTable {
+    spans: Spans,
+    /// JSON pointer of current value
+    path: String,
+}
+
+impl SpanTable {
+    fn record(&mut self, span: Span) {
+        let range = span.lo.to_usize()..span.hi.to_usize();
+        self.spans.insert(self.path.clone(), range);
+    }
+
+    /// move spans under `from` to `to`, after collapsing a single element array
+    fn rebase(&mut self, from: &str, to: &str) {
+        let moved: Vec<String> = self
+            .spans
+            .keys()
+            .filter(|x| {
+                x.strip_prefix(from)
+                    .is_some_and(|x| x.is_empty() || x.starts_with('/'))
+            })
+            .cloned()
+            .collect();
+        for key in moved {
+            let range = self.spans.remove(&key).unwrap();
+            self.spans
+                .insert(format!("{}{}", to, &key[from.len()..]), range);
+        }
+    }
+}
+
+/// state of a single parse
+#[derive(Default)]
+struct Context {
+    /// only for [`parse_source_spanned`]
+    spans: Option<SpanTable>,
+    /// top-level string and number bindings
+    symbols: HashMap<String, Value>,
+}
+
+impl Context {
+    /// run `f` with spans pointing at child `token`
+    fn child<R>(&mut self, token: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        let Some(table) = &mut self.spans else {
+            return f(self);
+        };
+
+        let len = table.path.len();
+        push_token(&mut table.path, token);
+        let ret = f(self);
+        if let Some(table) = &mut self.spans {
+            table.path.truncate(len);
+        }
+        ret
+    }
+
+    /// run `f` without recording spans, e.g. for operands
+    fn untracked<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let spans = self.spans.take();
+        let ret = f(self);
+        self.spans = spans;
+        ret
+    }
+}
+
 pub fn parse_source(source: &str) -> Result<Value, ParseError> {
+    parse(source, &mut Context::default())
+}
+
+/// same as [`parse_source`], also returns where each value is in `source`
+pub fn parse_source_spanned(source: &str) -> Result<(Value, Spans), ParseError> {
+    let mut cx = Context {
+        spans: Some(SpanTable::default()),
+        ..Default::default()
+    };
+    let value = parse(source, &mut cx)?;
+
+    Ok((value, cx.spans.unwrap().spans))
+}
+
+/// append an escaped JSON pointer token
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn parse(source: &str, cx: &mut Context) -> Result<Value, ParseError> {
     let source_file = StringInput::new(source, BytePos(0), BytePos::from_usize(source.len()));
     let mut parser = Parser::new(Default::default(), source_file, None);
-    let script = parser.parse_script()?;
+    let script = parser
+        .parse_script()
+        .map_err(|err| ParseError::from_swc(err, source))?;
 
-    parse_script(script).ok_or(ParseError {
+    parse_script(script, cx).ok_or(ParseError {
         kind: "parse_script error".into(),
         message: "not find any value in script".into(),
+        line: 0,
+        column: 0,
     })
 }
 
-fn parse_script(script: Script) -> Option<Value> {
+fn parse_script(script: Script, cx: &mut Context) -> Option<Value> {
+    collect_symbols(&script, cx);
+
     let mut array = Vec::new();
     for stmt in script.body {
-        if let Some(value) = parse_stmt(stmt) {
+        let value = cx.child(&array.len().to_string(), |x| parse_stmt(stmt, x));
+        if let Some(value) = value {
             array.push(value);
         }
     }
 
     match array.len() {
         0 => None,
-        1 => array.pop(),
+        1 => {
+            if let Some(table) = &mut cx.spans {
+                table.rebase("/0", "");
+            }
+            array.pop()
+        }
         _ => Some(Value::Array(array)),
     }
 }
 
-fn parse_stmt(stmt: Stmt) -> Option<Value> {
+/// string and number values of top-level `var`/`const`, in order so later
+/// ones can refer to earlier ones. object and array values are not cloned
+fn collect_symbols(script: &Script, cx: &mut Context) {
+    for stmt in &script.body {
+        let Stmt::Decl(Decl::Var(var)) = stmt else {
+            continue;
+        };
+        for decl in &var.decls {
+            let (Pat::Ident(name), Some(init)) = (&decl.name, &decl.init) else {
+                continue;
+            };
+            let scalar = matches!(
+                **init,
+                Expr::Lit(_)
+                    | Expr::Bin(_)
+                    | Expr::Tpl(_)
+                    | Expr::Unary(_)
+                    | Expr::Paren(_)
+                    | Expr::Ident(_)
+            );
+            if !scalar {
+                continue;
+            }
+
+            let value = cx.untracked(|x| parse_value(*init.clone(), x));
+            if let Some(value @ (Value::String(_) | Value::Number(_))) = value {
+                cx.symbols.insert(name.sym.to_string(), value);
+            }
+        }
+    }
+}
+
+fn parse_stmt(stmt: Stmt, cx: &mut Context) -> Option<Value> {
     match stmt {
         Stmt::Decl(decl) => {
-            let Some(inits) = parse_decl(decl) else {
-                return None;
-            };
+            let inits = parse_decl(decl)?;
             let mut values = Vec::new();
             for init in inits {
-                if let Some(value) = parse_expr(*init) {
+                let value = cx.child(&values.len().to_string(), |x| parse_expr(init, x));
+                if let Some(value) = value {
                     values.push(value);
                 }
             }
 
             match values.len() {
                 0 => None,
-                1 => Some(values.pop().unwrap()),
+                1 => {
+                    if let Some(table) = &mut cx.spans {
+                        let path = table.path.clone();
+                        table.rebase(&format!("{}/0", path), &path);
+                    }
+                    Some(values.pop().unwrap())
+                }
                 _ => Some(Value::Array(values)),
             }
         }
@@ -81,14 +252,29 @@ fn parse_stmt(stmt: Stmt) -> Option<Value> {
 }
 
 #[inline]
-fn parse_decl(decl: Decl) -> Option<Vec<Box<Expr>>> {
+fn parse_decl(decl: Decl) -> Option<Vec<Expr>> {
     match decl {
-        Decl::Var(var) => Some(var.decls.into_iter().filter_map(|x| x.init).collect()),
+        Decl::Var(var) => Some(
+            var.decls
+                .into_iter()
+                .filter_map(|x| x.init.map(|init| *init))
+                .collect(),
+        ),
         _ => None,
     }
 }
 
-fn parse_expr(expr: Expr) -> Option<Value> {
+fn parse_expr(expr: Expr, cx: &mut Context) -> Option<Value> {
+    let span = expr.span();
+    let value = parse_value(expr, cx)?;
+    if let Some(table) = &mut cx.spans {
+        table.record(span);
+    }
+
+    Some(value)
+}
+
+fn parse_value(expr: Expr, cx: &mut Context) -> Option<Value> {
     match expr {
         Expr::Object(object) => {
             let props: Vec<KeyValueProp> = object
@@ -107,8 +293,11 @@ fn parse_expr(expr: Expr) -> Option<Value> {
             let mut map = serde_json::Map::new();
 
             for prop in props {
-                if let Some(value) = parse_expr(*prop.value) {
-                    let key = parse_prop_name(prop.key);
+                let Some(key) = parse_prop_name(prop.key, cx) else {
+                    continue;
+                };
+                let value = cx.child(&key, |x| parse_expr(*prop.value, x));
+                if let Some(value) = value {
                     map.insert(key, value);
                 }
             }
@@ -117,41 +306,57 @@ fn parse_expr(expr: Expr) -> Option<Value> {
         }
         Expr::Array(array_lit) => {
             let mut array = Vec::new();
-            let elems = array_lit.elems.into_iter().filter_map(|x| x);
+            let elems = array_lit.elems.into_iter().flatten();
             for elem in elems {
-                if let Some(value) = parse_expr(*elem.expr) {
+                let value = cx.child(&array.len().to_string(), |x| parse_expr(*elem.expr, x));
+                if let Some(value) = value {
                     array.push(value)
                 }
             }
             Some(Value::Array(array))
         }
         Expr::Lit(lit) => parse_lit(lit),
-        Expr::Unary(unary) => parse_unary(unary),
-        // Expr::Bin(_) => None,
-        // Expr::Ident(_) => None,
+        Expr::Unary(unary) => parse_unary(unary, cx),
+        Expr::Bin(bin) => parse_bin(bin, cx),
+        Expr::Tpl(tpl) => parse_tpl(tpl),
+        Expr::Paren(paren) => parse_value(*paren.expr, cx),
+        Expr::Ident(ident) => cx.symbols.get(ident.sym.as_str()).cloned(),
         // Expr::Fn(_) => None,
         // Expr::Arrow(_) => None,
         _ => None,
     }
 }
 
-#[inline]
-fn parse_prop_name(key: PropName) -> String {
+/// none if the key is not a constant, e.g. `[name()]`, the property is skipped
+fn parse_prop_name(key: PropName, cx: &mut Context) -> Option<String> {
     match key {
-        PropName::Str(str) => str.value.to_string(),
-        PropName::Ident(ident) => ident.sym.to_string(),
-        PropName::Num(num) => num.value.to_string(),
-        _ => panic!("unknown key type"),
+        PropName::Str(str) => Some(str.value.to_string()),
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Num(num) => Some(num.value.to_string()),
+        PropName::BigInt(bigint) => Some(bigint.value.to_string()),
+        // `["a" + "b"]: ...`
+        PropName::Computed(computed) => match cx.untracked(|x| parse_value(*computed.expr, x))? {
+            Value::String(str) => Some(str),
+            Value::Number(number) => Some(number.as_f64()?.to_string()),
+            _ => None,
+        },
     }
 }
 
+/// `0xFF` and `1e3` are evaluated by swc, out of f64 range is dropped.
+/// bigint is a number if fits in i64, else its digits as string
 #[inline]
 fn parse_lit(lit: Lit) -> Option<Value> {
     match lit {
         Lit::Str(str) => Some(Value::String(str.value.to_string())),
-        Lit::Num(num) => Some(Value::Number(
-            serde_json::Number::from_f64(num.value).unwrap(),
-        )),
+        Lit::Num(num) => serde_json::Number::from_f64(num.value).map(Value::Number),
+        Lit::BigInt(bigint) => {
+            let digits = bigint.value.to_string();
+            match digits.parse::<i64>() {
+                Ok(int) => Some(Value::from(int)),
+                Err(_) => Some(Value::String(digits)),
+            }
+        }
         Lit::Bool(bool) => Some(Value::Bool(bool.value)),
         Lit::Null(_) => Some(Value::Null),
         _ => None,
@@ -160,18 +365,28 @@ fn parse_lit(lit: Lit) -> Option<Value> {
 
 /// I don't want spend too much time on this, so this only can handle minus number
 #[inline]
-fn parse_unary(unary: UnaryExpr) -> Option<Value> {
+fn parse_unary(unary: UnaryExpr, cx: &mut Context) -> Option<Value> {
     match unary.op {
         UnaryOp::Minus => {
-            if let Some(Value::Number(number)) = parse_expr(*unary.arg) {
-                let num = number.as_f64().unwrap();
-                Some(Value::Number(serde_json::Number::from_f64(-num).unwrap()))
-            } else {
-                None
+            // digits past i64 are kept as string, -i64::MIN only fits negated
+            if let Expr::Lit(Lit::BigInt(bigint)) = &*unary.arg {
+                let digits = format!("-{}", bigint.value);
+                return match digits.parse::<i64>() {
+                    Ok(int) => Some(Value::from(int)),
+                    Err(_) => Some(Value::String(digits)),
+                };
+            }
+            let Some(Value::Number(number)) = cx.untracked(|x| parse_value(*unary.arg, x)) else {
+                return None;
+            };
+            // keep bigint exact
+            if let Some(int) = number.as_i64() {
+                return int.checked_neg().map(Value::from);
             }
+            serde_json::Number::from_f64(-number.as_f64()?).map(Value::Number)
         }
         UnaryOp::Plus => {
-            if let ret @ Some(Value::Number(_)) = parse_expr(*unary.arg) {
+            if let ret @ Some(Value::Number(_)) = cx.untracked(|x| parse_value(*unary.arg, x)) {
                 ret
             } else {
                 None
@@ -181,43 +396,348 @@ fn parse_unary(unary: UnaryExpr) -> Option<Value> {
     }
 }
 
+/// minified `var <name> = <value>;`, which [`parse_source`] reads back the same
+pub fn value_to_js(name: &str, value: &Value) -> String {
+    // json is valid js, except line/paragraph separators in strings for
+    // older engines. they only appear in strings, as json escapes the rest
+    let json = serde_json::to_string(value)
+        .unwrap()
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+
+    format!("var {} = {};", name, json)
+}
+
+/// name can be used by [`value_to_js`]
+pub fn is_js_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|x| x.is_alphabetic() || x == '_' || x == '$')
+        && chars.all(|x| x.is_alphanumeric() || x == '_' || x == '$')
+}
+
+/// only `+` of two strings or two numbers, identifiers are resolved first
+#[inline]
+fn parse_bin(bin: BinExpr, cx: &mut Context) -> Option<Value> {
+    if bin.op != BinaryOp::Add {
+        return None;
+    }
+
+    let left = cx.untracked(|x| parse_value(*bin.left, x))?;
+    let right = cx.untracked(|x| parse_value(*bin.right, x))?;
+    match (left, right) {
+        (Value::String(left), Value::String(right)) => Some(Value::String(left + right.as_str())),
+        (Value::Number(left), Value::Number(right)) => Some(Value::Number(
+            serde_json::Number::from_f64(left.as_f64()? + right.as_f64()?)?,
+        )),
+        _ => None,
+    }
+}
+
+/// template literal without `${...}`
+#[inline]
+fn parse_tpl(tpl: Tpl) -> Option<Value> {
+    if !tpl.exprs.is_empty() {
+        return None;
+    }
+
+    let mut text = String::new();
+    for quasi in tpl.quasis {
+        text.push_str(quasi.cooked?.as_str());
+    }
+    Some(Value::String(text))
+}
+
+/// keys an object must have to be deserialized, see `#[derive(CondKeys)]`
 pub trait CondKeys {
     fn keys<'a>() -> &'a [&'a str];
 }
 
+pub use cwa_images_derive::CondKeys;
+
+/// nesting depth searched by default, far deeper than any list
+pub const MAX_DEPTH: usize = 128;
+
+/// objects having all keys of `T` but failed to deserialize, e.g. `img` not
+/// a string, are skipped with a warning
 pub fn find_objects<T: CondKeys + DeserializeOwned>(value: Value) -> Vec<T> {
+    find_objects_with_depth(value, MAX_DEPTH)
+}
+
+/// same as [`find_objects`], but fails on the first object having all keys
+/// of `T` that can not be deserialized
+pub fn try_find_objects<T: CondKeys + DeserializeOwned>(
+    value: Value,
+) -> Result<Vec<T>, serde_json::Error> {
+    let mut array = Vec::new();
+    find_at(value, false, MAX_DEPTH, &mut String::new(), &mut array);
+    array.into_iter().map(|(_, x)| x).collect()
+}
+
+/// same as [`find_objects`], objects nested deeper than `max_depth` are not
+/// looked at, e.g. of a pathological script. the root is depth 0
+pub fn find_objects_with_depth<T: CondKeys + DeserializeOwned>(
+    value: Value,
+    max_depth: usize,
+) -> Vec<T> {
+    let mut array = Vec::new();
+    find_at(value, false, max_depth, &mut String::new(), &mut array);
+    skip_invalid(array).into_iter().map(|(_, x)| x).collect()
+}
+
+/// same as [`find_objects`], but with `ignore_case` keys are also matched
+/// case-insensitively (e.g. `Img` for `img`) and renamed before deserialize
+pub fn find_objects_with<T: CondKeys + DeserializeOwned>(
+    value: Value,
+    ignore_case: bool,
+) -> Vec<T> {
+    let mut array = Vec::new();
+    find_at(
+        value,
+        ignore_case,
+        MAX_DEPTH,
+        &mut String::new(),
+        &mut array,
+    );
+    skip_invalid(array).into_iter().map(|(_, x)| x).collect()
+}
+
+/// same as [`find_objects`], with path of each object in js syntax, e.g.
+/// `data.items[3]` or `data["0-1"]`. the root is an empty path
+pub fn find_objects_with_paths<T: CondKeys + DeserializeOwned>(value: Value) -> Vec<(String, T)> {
     let mut array = Vec::new();
+    find_at(value, false, MAX_DEPTH, &mut String::new(), &mut array);
+    skip_invalid(array)
+}
+
+/// `depth` is how much deeper to look
+fn find_at<T: CondKeys + DeserializeOwned>(
+    value: Value,
+    ignore_case: bool,
+    depth: usize,
+    path: &mut String,
+    array: &mut Vec<(String, Result<T, serde_json::Error>)>,
+) {
+    let len = path.len();
     match value {
         Value::Object(map) => {
-            let matched = T::keys().iter().all(|x| map.contains_key(*x));
-            if matched {
-                if let Ok(val) = serde_json::from_value(Value::Object(map.clone())) {
-                    array.push(val);
+            match match_object(&map, ignore_case) {
+                Ok(Some(val)) => {
+                    array.push((path.clone(), Ok(val)));
+                    return;
+                }
+                // may still contain matching objects
+                Err(err) => array.push((path.clone(), Err(err))),
+                Ok(None) => {}
+            }
+
+            if depth == 0 {
+                return;
+            }
+            for (key, val) in map {
+                push_key(path, &key);
+                find_at(val, ignore_case, depth - 1, path, array);
+                path.truncate(len);
+            }
+        }
+        Value::Array(elems) if depth > 0 => {
+            for (i, elem) in elems.into_iter().enumerate() {
+                path.push_str(&format!("[{}]", i));
+                find_at(elem, ignore_case, depth - 1, path, array);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// drop objects failed to deserialize, with a warning
+fn skip_invalid<T>(found: Vec<(String, Result<T, serde_json::Error>)>) -> Vec<(String, T)> {
+    found
+        .into_iter()
+        .filter_map(|(path, val)| match val {
+            Ok(val) => Some((path, val)),
+            Err(err) => {
+                log::warn!("skipped object at {}: {}", display_path(&path), err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "root"
+    } else {
+        path
+    }
+}
+
+/// `.key`, or `["key"]` if not an identifier
+fn push_key(path: &mut String, key: &str) {
+    if is_js_identifier(key) {
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key);
+    } else {
+        path.push('[');
+        path.push_str(&Value::String(key.to_string()).to_string());
+        path.push(']');
+    }
+}
+
+/// same as [`find_objects_with`], with byte range of each object in source,
+/// `spans` from [`parse_source_spanned`]
+pub fn find_objects_spanned<T: CondKeys + DeserializeOwned>(
+    value: Value,
+    spans: &Spans,
+    ignore_case: bool,
+) -> Vec<(T, Range<usize>)> {
+    let mut array = Vec::new();
+    let path = &mut String::new();
+    find_spanned_at(value, spans, ignore_case, MAX_DEPTH, path, &mut array);
+    array
+}
 
-                    return array;
+fn find_spanned_at<T: CondKeys + DeserializeOwned>(
+    value: Value,
+    spans: &Spans,
+    ignore_case: bool,
+    depth: usize,
+    path: &mut String,
+    array: &mut Vec<(T, Range<usize>)>,
+) {
+    let len = path.len();
+    match value {
+        Value::Object(map) => {
+            match match_object(&map, ignore_case) {
+                Ok(Some(val)) => {
+                    if let Some(range) = spans.get(path.as_str()) {
+                        array.push((val, range.clone()));
+                    }
+                    return;
                 }
+                Err(err) => log::warn!("skipped object at {}: {}", display_path(path), err),
+                Ok(None) => {}
+            }
+
+            if depth == 0 {
+                return;
+            }
+            for (key, val) in map {
+                push_token(path, &key);
+                find_spanned_at(val, spans, ignore_case, depth - 1, path, array);
+                path.truncate(len);
+            }
+        }
+        Value::Array(elems) if depth > 0 => {
+            for (i, elem) in elems.into_iter().enumerate() {
+                push_token(path, &i.to_string());
+                find_spanned_at(elem, spans, ignore_case, depth - 1, path, array);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// deserialize `map` if it has all keys of `T`
+fn match_object<T: CondKeys + DeserializeOwned>(
+    map: &Map<String, Value>,
+    ignore_case: bool,
+) -> Result<Option<T>, serde_json::Error> {
+    let object = if ignore_case {
+        normalize_keys(T::keys(), map)
+    } else if T::keys().iter().all(|x| map.contains_key(*x)) {
+        Some(map.clone())
+    } else {
+        None
+    };
+
+    object
+        .map(|x| serde_json::from_value(Value::Object(x)))
+        .transpose()
+}
+
+/// rename keys which case-insensitively equal to `keys`, exact match first
+fn normalize_keys(keys: &[&str], map: &Map<String, Value>) -> Option<Map<String, Value>> {
+    let mut renames = Vec::new();
+    for key in keys {
+        if map.contains_key(*key) {
+            continue;
+        }
+        let found = map.keys().find(|x| x.eq_ignore_ascii_case(key))?;
+        renames.push((found.as_str(), *key));
+    }
+
+    let mut object = map.clone();
+    for (from, to) in renames {
+        if let Some(val) = object.remove(from) {
+            object.insert(to.to_string(), val);
+        }
+    }
+    Some(object)
+}
+
+/// find objects that *almost* match: having a string value keyed by `key`
+/// case-insensitively. returns the actual key names of each object, as a hint
+/// when the list format changed and nothing matched
+pub fn schema_hints(value: &Value, key: &str) -> Vec<Vec<String>> {
+    let mut hints: Vec<Vec<String>> = schema_hints_at(value, key)
+        .into_iter()
+        .map(|(_, keys)| keys)
+        .collect();
+    hints.dedup();
+    hints
+}
+
+/// same as [`schema_hints`] without dedup, with JSON pointer of each object
+pub fn schema_hints_at(value: &Value, key: &str) -> Vec<(String, Vec<String>)> {
+    let mut hints = Vec::new();
+    hints_at(value, key, &mut String::new(), &mut hints);
+    hints
+}
+
+fn hints_at(value: &Value, key: &str, path: &mut String, hints: &mut Vec<(String, Vec<String>)>) {
+    let len = path.len();
+    match value {
+        Value::Object(map) => {
+            let near = map
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case(key) && v.is_string());
+            if near {
+                hints.push((path.clone(), map.keys().cloned().collect()));
             }
 
-            for (_, val) in map {
-                array.extend(find_objects(val));
+            for (k, val) in map {
+                push_token(path, k);
+                hints_at(val, key, path, hints);
+                path.truncate(len);
             }
         }
         Value::Array(elems) => {
-            for elem in elems {
-                array.extend(find_objects(elem));
+            for (i, elem) in elems.iter().enumerate() {
+                push_token(path, &i.to_string());
+                hints_at(elem, key, path, hints);
+                path.truncate(len);
             }
         }
         _ => {}
     }
-    array
 }
 
 #[cfg(test)]
 mod tests {
     use serde::Deserialize;
-    use serde_json::Value;
+    use serde_json::{json, Value};
 
-    use super::{find_objects, parse_source, CondKeys};
+    use super::{
+        find_objects, find_objects_spanned, find_objects_with, find_objects_with_depth,
+        find_objects_with_paths, is_js_identifier, parse_source, parse_source_spanned,
+        schema_hints, schema_hints_at, try_find_objects, value_to_js, CondKeys,
+    };
 
     const SOURCE: &str = r#"var data = {
     "object_key": {
@@ -247,7 +767,7 @@ mod tests {
         "float_key": 3.1415926,
         "array_key": [1.0, 12.0, -24.0, 3.1415926, -0.3, true, false, null, "Hello World", {"object_in_array": true}]
     },
-    "illegal stuff": [["down", "here"]],
+    "illegal stuff": [["down", "here"], "this is killing me"],
     "chinese": "這可以處理中文嗎?", "english": "can this handle same line?",
     "3.1415926": "float(pi)",
     "true": "bool",
@@ -257,6 +777,19 @@ mod tests {
     "end": true
     }"#;
 
+    #[test]
+    fn test_parse_error_position() {
+        let err = parse_source("var a = {\n  img: 'a.jpg',\n  text: ]\n};").unwrap_err();
+        assert_eq!((3, 9), (err.line, err.column));
+        assert!(err.to_string().contains("line 3, column 9"), "{}", err);
+
+        let err = parse_source("// 中文\nvar a = ;").unwrap_err();
+        assert_eq!((2, 9), (err.line, err.column));
+
+        let err = parse_source("var a;").unwrap_err();
+        assert_eq!(0, err.line);
+    }
+
     #[test]
     fn test_parser() {
         let value = parse_source(SOURCE).unwrap();
@@ -281,7 +814,7 @@ mod tests {
 
     impl CondKeys for TryThis {
         fn keys<'a>() -> &'a [&'a str] {
-            return &["string", "number", "bool"];
+            &["string", "number", "bool"]
         }
     }
 
@@ -298,4 +831,352 @@ mod tests {
         let object = objects.pop().unwrap();
         assert_eq!(expect, object);
     }
+
+    /// `{"a": [{"a": ... {"img": .., "text": ..}}]}`, `levels` of object
+    /// and array each
+    fn nested(levels: usize) -> Value {
+        let mut value = json!({"img": "deep.png", "text": "deep"});
+        for _ in 0..levels {
+            value = json!({"a": [value]});
+        }
+        value
+    }
+
+    #[test]
+    fn test_find_objects_with_paths() {
+        let value = json!({
+            "data": {
+                "items": [
+                    {"note": "no img"},
+                    {"img": "a.png", "text": "a"}
+                ],
+                "0-1": {"img": "b.png", "text": "b"}
+            }
+        });
+        let objects = find_objects_with_paths::<ImgLike>(value);
+        let paths: Vec<&str> = objects.iter().map(|(x, _)| x.as_str()).collect();
+        assert_eq!(vec![r#"data["0-1"]"#, "data.items[1]"], paths);
+        assert_eq!("a.png", objects[1].1.img);
+
+        let root = json!({"img": "a.png", "text": "a"});
+        assert_eq!("", find_objects_with_paths::<ImgLike>(root)[0].0);
+    }
+
+    #[test]
+    fn test_try_find_objects() {
+        let value = json!({
+            "ok": {"img": "a.png", "text": "a"},
+            "bad": {"img": 1, "text": "b", "inner": {"img": "c.png", "text": "c"}}
+        });
+        assert!(try_find_objects::<ImgLike>(value.clone()).is_err());
+        // skipped, but objects inside are still found
+        let objects = find_objects_with_paths::<ImgLike>(value);
+        let paths: Vec<&str> = objects.iter().map(|(x, _)| x.as_str()).collect();
+        assert_eq!(vec!["bad.inner", "ok"], paths);
+
+        let value = json!([{"img": "a.png", "text": "a"}, {"note": "no img"}]);
+        assert_eq!(1, try_find_objects::<ImgLike>(value).unwrap().len());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        // root object is depth 0, its array 1, the image object 2
+        let objects = find_objects_with_depth::<ImgLike>(nested(1), 2);
+        assert_eq!("deep.png", objects[0].img);
+        assert!(find_objects_with_depth::<ImgLike>(nested(1), 1).is_empty());
+        let value = json!({"img": "top.png", "text": "top", "a": [nested(1)]});
+        assert_eq!(1, find_objects_with_depth::<ImgLike>(value, 0).len());
+
+        // found above the limit, nothing below
+        let value = json!([
+            {"img": "top.png", "text": "top"},
+            nested(200)
+        ]);
+        let objects = find_objects::<ImgLike>(value.clone());
+        assert_eq!(
+            vec!["top.png"],
+            objects.iter().map(|x| &x.img).collect::<Vec<_>>()
+        );
+        assert_eq!(2, find_objects_with_depth::<ImgLike>(value, 402).len());
+    }
+
+    /// `TryThis` by the derive
+    #[derive(Debug, PartialEq, Deserialize, CondKeys)]
+    struct Derived {
+        string: String,
+        number: f64,
+        bool: bool,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, CondKeys)]
+    struct Renamed {
+        #[serde(rename = "string")]
+        text: String,
+        #[serde(default)]
+        #[condkeys(skip)]
+        missing: Option<String>,
+        #[serde(flatten)]
+        extra: serde_json::Map<String, Value>,
+    }
+
+    #[test]
+    fn test_derive_cond_keys() {
+        assert_eq!(["string", "number", "bool"], Derived::keys());
+        let value = parse_source(SOURCE).unwrap();
+        assert_eq!(
+            find_objects::<TryThis>(value.clone()).len(),
+            find_objects::<Derived>(value).len()
+        );
+        assert_eq!(["string"], Renamed::keys());
+        assert_eq!(["img", "text"], crate::task::Img::keys());
+
+        let value = parse_source(SOURCE2).unwrap();
+        let objects = find_objects::<Renamed>(value);
+        assert_eq!(1, objects.len());
+        assert_eq!("hello", objects[0].text);
+        assert_eq!(None, objects[0].missing);
+        assert_eq!(2, objects[0].extra.len());
+    }
+
+    const SOURCE3: &str = r#"var data = {
+        "list": {
+            "items": [
+                {"Img": "a.png", "Text": "a"},
+                {"Img": "b.png", "Text": "b"}
+            ]
+        }
+    }"#;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct ImgLike {
+        img: String,
+        text: String,
+    }
+
+    impl CondKeys for ImgLike {
+        fn keys<'a>() -> &'a [&'a str] {
+            &["img", "text"]
+        }
+    }
+
+    #[test]
+    fn test_renamed_keys() {
+        let value = parse_source(SOURCE3).unwrap();
+        assert!(find_objects::<ImgLike>(value.clone()).is_empty());
+
+        let hints = schema_hints(&value, "img");
+        assert_eq!(vec![vec!["Img".to_string(), "Text".to_string()]], hints);
+
+        let objects = find_objects_with::<ImgLike>(value, true);
+        assert_eq!(
+            vec![
+                ImgLike {
+                    img: "a.png".into(),
+                    text: "a".into()
+                },
+                ImgLike {
+                    img: "b.png".into(),
+                    text: "b".into()
+                }
+            ],
+            objects
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let (value, spans) = parse_source_spanned(SOURCE).unwrap();
+        assert_eq!(parse_source(SOURCE).unwrap(), value);
+
+        let snippet = |pointer: &str| &SOURCE[spans[pointer].clone()];
+        assert_eq!(r#""string""#, snippet("/object_key/string_key"));
+        assert_eq!("-24", snippet("/object_key/array_key/2"));
+        assert_eq!(
+            r#"{"object_in_array": true}"#,
+            snippet("/object_key/array_key/9")
+        );
+        // index after a skipped element
+        assert_eq!(r#"["down", "here"]"#, snippet("/illegal stuff/0"));
+        assert_eq!(r#""this is" + " killing me""#, snippet("/illegal stuff/1"));
+        assert!(!spans.contains_key("/illegal stuff/2"));
+        assert!(snippet("").starts_with('{'));
+
+        let (value, spans) = parse_source_spanned(SOURCE3).unwrap();
+        assert!(find_objects_spanned::<ImgLike>(value.clone(), &spans, false).is_empty());
+        let hints = schema_hints_at(&value, "img");
+        assert_eq!("/list/items/1", hints[1].0);
+        assert_eq!(
+            r#"{"Img": "b.png", "Text": "b"}"#,
+            &SOURCE3[spans[&hints[1].0].clone()]
+        );
+
+        let objects = find_objects_spanned::<ImgLike>(value, &spans, true);
+        assert_eq!(2, objects.len());
+        for (object, range) in objects {
+            // the snippet alone parses to the same object
+            let snippet = format!("var x = {}", &SOURCE3[range]);
+            let found = find_objects_with::<ImgLike>(parse_source(&snippet).unwrap(), true);
+            assert_eq!(vec![object], found);
+        }
+    }
+
+    #[test]
+    fn test_spans_multiple_values() {
+        let source = "var a = [1, 2], b = {c: \"d\"};\nvar e = true;";
+        let (value, spans) = parse_source_spanned(source).unwrap();
+        assert_eq!(serde_json::json!([[[1.0, 2.0], {"c": "d"}], true]), value);
+        assert_eq!("2", &source[spans["/0/0/1"].clone()]);
+        assert_eq!(r#""d""#, &source[spans["/0/1/c"].clone()]);
+        assert_eq!("true", &source[spans["/1"].clone()]);
+    }
+
+    #[test]
+    fn test_value_to_js() {
+        let value = parse_source(SOURCE).unwrap();
+        let js = value_to_js("data", &value);
+        assert!(js.starts_with("var data = {"));
+        assert!(!js.contains('\n'));
+        assert_eq!(value, parse_source(&js).unwrap());
+
+        let value = serde_json::json!({
+            "text": "雷達回波 \"合成\"\\ 12:00",
+            "line": "a\u{2028}b\nc</script>",
+        });
+        let js = value_to_js("list", &value);
+        assert!(js.contains("雷達回波"));
+        assert!(js.contains("a\\u2028b\\nc"));
+        assert_eq!(value, parse_source(&js).unwrap());
+
+        assert!(is_js_identifier("$data_1"));
+        assert!(is_js_identifier("雷達"));
+        assert!(!is_js_identifier("1data"));
+        assert!(!is_js_identifier("data = 1; x"));
+        assert!(!is_js_identifier(""));
+    }
+
+    #[test]
+    fn test_concat() {
+        let source = r#"var name = "CV1_3600.png", data = {
+            "nested": "/Data/" + ("radar" + "/") + "CV1_" + "3600.png",
+            "number": 1 + 2 + -0.5,
+            "paren": ("a" + "b"),
+            "ident": "/Data/" + name,
+            "mixed": "CV1_" + 3600,
+            "minus": 3 - 1,
+        }"#;
+        let value = parse_source(source).unwrap();
+        let expect = serde_json::json!([
+            "CV1_3600.png",
+            {
+                "nested": "/Data/radar/CV1_3600.png",
+                "number": 2.5,
+                "paren": "ab",
+                "ident": "/Data/CV1_3600.png",
+            }
+        ]);
+        assert_eq!(expect, value);
+    }
+
+    #[test]
+    fn test_number_literal() {
+        let source = r#"var data = {
+            "hex": 0xFF,
+            "octal": 0o17,
+            "binary": 0b101,
+            "exponent": 1e3,
+            "negative": -2.5e-1,
+            "overflow": 1e400,
+            "bigint": 10n,
+            "negative_bigint": -0x10n,
+            "huge_bigint": 123456789012345678901234567890n,
+            "minus_huge": -123456789012345678901234567890n,
+            "i64_min": -9223372036854775808n,
+        }"#;
+        let value = parse_source(source).unwrap();
+        let expect = serde_json::json!({
+            "hex": 255.0,
+            "octal": 15.0,
+            "binary": 5.0,
+            "exponent": 1000.0,
+            "negative": -0.25,
+            "bigint": 10,
+            "negative_bigint": -16,
+            "huge_bigint": "123456789012345678901234567890",
+            "minus_huge": "-123456789012345678901234567890",
+            "i64_min": i64::MIN,
+        });
+        assert_eq!(expect, value);
+    }
+
+    #[test]
+    fn test_non_finite_number() {
+        let source = r#"var data = {
+            "infinity": Infinity,
+            "minus_infinity": -Infinity,
+            "nan": NaN,
+            "division": 1 / 0,
+            "overflow": -1e400,
+            "sum": 1e308 + 1e308,
+            "img": "a.png",
+        }"#;
+        let value = parse_source(source).unwrap();
+        assert_eq!(serde_json::json!({"img": "a.png"}), value);
+    }
+
+    #[test]
+    fn test_computed_key() {
+        let source = r#"var prefix = "CV1_", data = {
+            ["img"]: "a.png",
+            [prefix + "3600"]: 1,
+            [1 + 1]: "two",
+            [name()]: "skipped",
+            [{}]: "skipped",
+            10n: "bigint",
+        }"#;
+        let value = parse_source(source).unwrap();
+        let expect = serde_json::json!([
+            "CV1_",
+            {"img": "a.png", "CV1_3600": 1.0, "2": "two", "10": "bigint"}
+        ]);
+        assert_eq!(expect, value);
+    }
+
+    #[test]
+    fn test_template_literal() {
+        let source = r#"var data = {
+            "double": "/Data/radar/\u96f7\u9054\n",
+            "template": `/Data/radar/\u96f7\u9054\n`,
+            "interpolated": `/Data/${dir}/`,
+        }"#;
+        let value = parse_source(source).unwrap();
+        assert_eq!(value["double"], value["template"]);
+        assert_eq!("/Data/radar/雷達\n", value["template"]);
+        assert!(value.get("interpolated").is_none());
+    }
+
+    #[test]
+    fn test_identifier() {
+        let source = r#"
+            var base = "/Data/radar/";
+            const step = 10, half = step / 2;
+            var prefix = base + "CV1_", list = [1, 2];
+            var data = {
+                "img": prefix + "3600.png",
+                "dir": base,
+                "step": step,
+                "half": half,
+                "list": list,
+                "unknown": other,
+            };
+        "#;
+        let value = parse_source(source).unwrap();
+        let data = &value[3];
+        assert_eq!("/Data/radar/CV1_3600.png", data["img"]);
+        assert_eq!("/Data/radar/", data["dir"]);
+        assert_eq!(10.0, data["step"]);
+        // only string and number bindings
+        assert!(data.get("half").is_none());
+        assert!(data.get("list").is_none());
+        assert!(data.get("unknown").is_none());
+    }
 }
@@ -0,0 +1,98 @@
+//! `--refresh`, existing files are requested again and overwritten only if
+//! the remote copy changed, e.g. a corrected chart under the same name
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::RequestBuilder;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// ETags of files saved by this process
+#[derive(Debug, Default)]
+pub struct Refresh(Mutex<HashMap<PathBuf, String>>);
+
+impl Refresh {
+    /// add `If-Modified-Since` of the mtime of `path`, and `If-None-Match`
+    /// if saved by this process
+    pub fn conditional(&self, path: &Path, req: RequestBuilder) -> RequestBuilder {
+        let mut req = req;
+        if let Some(etag) = self.0.lock().unwrap().get(path) {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Ok(modified) = std::fs::metadata(path).and_then(|x| x.modified()) {
+            req = req.header(IF_MODIFIED_SINCE, http_date(modified));
+        }
+        req
+    }
+
+    /// keep ETag of the saved `path`, and set its mtime to `Last-Modified`
+    pub fn saved(&self, path: &Path, headers: &HeaderMap) -> io::Result<()> {
+        let header = |name| headers.get(name).and_then(|x| x.to_str().ok());
+        if let Some(etag) = header(ETAG) {
+            let mut etags = self.0.lock().unwrap();
+            etags.insert(path.to_path_buf(), etag.to_string());
+        }
+        if let Some(modified) = header(LAST_MODIFIED).and_then(parse_http_date) {
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(modified)?;
+        }
+        Ok(())
+    }
+}
+
+/// e.g. `Wed, 24 Jul 2024 12:00:00 GMT`
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn parse_http_date(text: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(text)
+        .ok()
+        .map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_date() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1721822400);
+        assert_eq!("Wed, 24 Jul 2024 12:00:00 GMT", http_date(time));
+        assert_eq!(Some(time), parse_http_date(&http_date(time)));
+        assert_eq!(None, parse_http_date("yesterday"));
+    }
+
+    #[test]
+    fn test_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("frame.png");
+        std::fs::write(&path, "png").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Wed, 24 Jul 2024 12:00:00 GMT"),
+        );
+
+        let refresh = Refresh::default();
+        refresh.saved(&path, &headers).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1721822400),
+            modified
+        );
+        assert_eq!(
+            Some("\"v1\""),
+            refresh.0.lock().unwrap().get(&path).map(|x| x.as_str())
+        );
+    }
+}
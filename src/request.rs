@@ -0,0 +1,653 @@
+//! http requests of lists and images
+#[cfg(feature = "faults")]
+use crate::fault;
+use crate::host::Host;
+use crate::schedule;
+use futures_util::TryStreamExt;
+use imagesize::ImageSize;
+use log::{debug, error};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RANGE, USER_AGENT};
+use reqwest::{Client, Proxy, RequestBuilder, Response, StatusCode};
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, Sleep};
+use tokio_util::io::StreamReader;
+use url::Url;
+
+/// http client bound to a host, lists and images are resolved against it
+#[derive(Debug, Clone, Default)]
+pub struct CwaClient {
+    pub host: Host,
+    pub http: Client,
+    pub retry: Retry,
+    /// shared by clones, e.g. of concurrent downloads
+    pub rate_limit: Option<Arc<RateLimit>>,
+    /// bytes per second of all image bodies
+    pub bandwidth: Option<Arc<Bandwidth>>,
+}
+
+/// token bucket of a single token, requests are spaced evenly by the rate
+#[derive(Debug)]
+pub struct RateLimit {
+    interval: Duration,
+    /// when the next request may be sent
+    next: Mutex<Instant>,
+}
+
+impl RateLimit {
+    /// `per_second` must be positive, e.g. 0.5 for one request every 2s
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// wait for the turn of a request
+    pub async fn acquire(&self) {
+        let now = Instant::now();
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(now);
+            *next = at + self.interval;
+            at
+        };
+        let delay = at - now;
+        if delay > Duration::from_secs(1) {
+            debug!(
+                "rate limited, waiting {}",
+                humantime::format_duration(delay)
+            );
+        }
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// bytes per second shared by all downloads, e.g. of `--limit-rate 200K`
+#[derive(Debug)]
+pub struct Bandwidth {
+    per_second: u64,
+    /// when the next chunk may be read
+    next: Mutex<Instant>,
+}
+
+impl Bandwidth {
+    pub fn new(per_second: u64) -> Self {
+        Self {
+            per_second: per_second.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// count `bytes` just read, returns when the next read may start
+    fn consume(&self, bytes: usize) -> Instant {
+        let now = Instant::now();
+        let mut next = self.next.lock().unwrap();
+        let at = (*next).max(now) + Duration::from_secs_f64(bytes as f64 / self.per_second as f64);
+        *next = at;
+        at
+    }
+}
+
+/// reader that waits after each chunk, so all of them stay under `bandwidth`.
+/// a single chunk may go over
+pub struct Throttle<R> {
+    inner: R,
+    bandwidth: Option<Arc<Bandwidth>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> Throttle<R> {
+    pub fn new(inner: R, bandwidth: Option<Arc<Bandwidth>>) -> Self {
+        Self {
+            inner,
+            bandwidth,
+            sleep: None,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Throttle<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some(sleep) = &mut self.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            self.sleep = None;
+        }
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - filled;
+        if let (Some(bandwidth), 1..) = (&self.bandwidth, read) {
+            let at = bandwidth.consume(read);
+            self.sleep = Some(Box::pin(tokio::time::sleep_until(at)));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// `--rate-limit`, requests per second
+pub fn parse_rate(text: &str) -> Result<f64, String> {
+    match text.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!(
+            "expect requests per second more than 0, e.g. 2 or 0.5, got {:?}",
+            text
+        )),
+    }
+}
+
+/// retries of failed requests, by timeout, connection error or 5xx
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    pub retries: u32,
+    /// delay before the first retry, doubled for each one after
+    pub backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Retry {
+    /// delay before `retry`, 1-based, with jitter of up to half of it
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        delay + schedule::jitter(delay / 2)
+    }
+
+    /// call `request` until it succeeds, fails for good or retries run out
+    pub async fn run<F, Fut>(&self, url: &Url, mut request: F) -> Result<Response, Box<dyn Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response, Box<dyn Error>>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Ok(resp) => return Ok(resp),
+                Err(err) if is_retryable(err.as_ref()) => {
+                    if attempt > self.retries {
+                        if attempt > 1 {
+                            error!("{} failed after {} attempts", url, attempt);
+                        }
+                        return Err(err);
+                    }
+                    let delay = self.delay(attempt);
+                    debug!(
+                        "attempt {} of {} failed, retry in {}: {}",
+                        attempt,
+                        url,
+                        humantime::format_duration(delay),
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// timeout, connection error or 5xx, 4xx fails at once
+fn is_retryable(err: &(dyn Error + 'static)) -> bool {
+    let Some(err) = err.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+    err.is_timeout() || err.is_connect() || err.status().is_some_and(|x| x.is_server_error())
+}
+
+/// error status of a failed request
+pub fn status_of(err: &(dyn Error + 'static)) -> Option<StatusCode> {
+    err.downcast_ref::<reqwest::Error>()?.status()
+}
+
+/// settings of the http client, for lists and images alike
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// zero is no timeout
+    pub connect_timeout: Duration,
+    /// between reads of a response, not the whole download. zero is no timeout
+    pub read_timeout: Duration,
+    /// whole request including the body. zero is no timeout
+    pub timeout: Duration,
+    pub user_agent: Option<HeaderValue>,
+    /// sent with every request, after `user_agent`
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// all requests go through it, `HTTP_PROXY`/`HTTPS_PROXY` are used if none
+    pub proxy: Option<Url>,
+    /// of lists and images
+    pub retries: u32,
+    /// requests per second
+    pub rate_limit: Option<f64>,
+    /// bytes per second of image bodies
+    pub limit_rate: Option<u64>,
+}
+
+impl CwaClient {
+    pub fn new(host: Host) -> Self {
+        Self {
+            host,
+            http: Client::new(),
+            retry: Retry::default(),
+            rate_limit: None,
+            bandwidth: None,
+        }
+    }
+
+    /// body of an image as a reader, throttled by `bandwidth`
+    pub fn body(&self, resp: Response) -> Throttle<impl AsyncRead + Unpin> {
+        let stream = StreamReader::new(resp.bytes_stream().map_err(io::Error::other));
+        Throttle::new(stream, self.bandwidth.clone())
+    }
+
+    /// wait for `rate_limit` before sending a request
+    pub async fn throttle(&self) {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire().await;
+        }
+    }
+
+    /// GET that fails on error status, see [`CwaClient::send`]
+    pub async fn get(&self, url: Url) -> Result<Response, Box<dyn Error>> {
+        self.send(&url, || self.http.get(url.clone())).await
+    }
+
+    /// send the request built by `request` to `url`, with rate limit, retries
+    /// and injected faults. fails on error status
+    pub async fn send<F>(&self, url: &Url, request: F) -> Result<Response, Box<dyn Error>>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let send = || async {
+            self.throttle().await;
+            let resp = request().send().await?;
+            #[cfg(feature = "faults")]
+            let resp = fault::response(resp).await?;
+
+            // tf?
+            Ok(resp.error_for_status()?)
+        };
+        self.retry.run(url, send).await
+    }
+
+    pub fn build(host: Host, opts: &ClientOptions) -> Result<Self, reqwest::Error> {
+        let mut builder = Client::builder();
+        if !opts.connect_timeout.is_zero() {
+            builder = builder.connect_timeout(opts.connect_timeout);
+        }
+        if !opts.read_timeout.is_zero() {
+            builder = builder.read_timeout(opts.read_timeout);
+        }
+        if !opts.timeout.is_zero() {
+            builder = builder.timeout(opts.timeout);
+        }
+
+        let mut headers = HeaderMap::new();
+        if let Some(user_agent) = &opts.user_agent {
+            headers.insert(USER_AGENT, user_agent.clone());
+        }
+        for (name, value) in &opts.headers {
+            headers.append(name, value.clone());
+        }
+        builder = builder.default_headers(headers);
+        if let Some(proxy) = &opts.proxy {
+            builder = builder.proxy(Proxy::all(proxy.clone())?);
+        }
+
+        Ok(Self {
+            host,
+            http: builder.build()?,
+            retry: Retry {
+                retries: opts.retries,
+                ..Default::default()
+            },
+            rate_limit: opts.rate_limit.map(|x| Arc::new(RateLimit::new(x))),
+            bandwidth: opts.limit_rate.map(|x| Arc::new(Bandwidth::new(x))),
+        })
+    }
+}
+
+/// `--header`, `Name: Value`
+pub fn parse_header(text: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = text
+        .split_once(':')
+        .ok_or_else(|| format!("expect \"Name: Value\", got {:?}", text))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name {:?}", name.trim()))?;
+    Ok((name, parse_header_value(value)?))
+}
+
+/// `--proxy`, http(s) or socks5 url. e.g. socks5h://127.0.0.1:1080
+pub fn parse_proxy(text: &str) -> Result<Url, String> {
+    let invalid = || format!("expect http, https, socks5 or socks5h url, got {:?}", text);
+    let url = Url::parse(text.trim()).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") || url.host().is_none() {
+        return Err(invalid());
+    }
+    Ok(url)
+}
+
+/// connect to the proxy once, so an unreachable proxy is reported by name
+/// instead of as failed requests
+pub async fn check_proxy(proxy: &Url, timeout: Duration) -> Result<(), String> {
+    let host = proxy.host_str().unwrap_or_default();
+    // socks has no known default of url crate
+    let port = proxy.port_or_known_default().unwrap_or(1080);
+    let connect = TcpStream::connect((host.trim_matches(['[', ']']), port));
+    let result = if timeout.is_zero() {
+        connect.await
+    } else {
+        tokio::time::timeout(timeout, connect)
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+    };
+    result
+        .map(|_| ())
+        .map_err(|err| format!("cannot connect to proxy {}:{} {}", host, port, err))
+}
+
+/// `--user-agent` and values of `--header`
+pub fn parse_header_value(text: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(text.trim())
+        .map_err(|_| format!("invalid header value {:?}", text.trim()))
+}
+
+/// bytes to fetch for reading image header
+pub const PROBE_SIZE: usize = 16 * 1024;
+
+pub enum Probe {
+    Size(ImageSize),
+    /// server ignored range, response of full image
+    Full(reqwest::Response),
+}
+
+/// read dimensions of image from the first bytes by range request
+pub async fn probe(client: &CwaClient, url: Url) -> Result<Probe, Box<dyn Error>> {
+    let request = || {
+        client
+            .http
+            .get(url.clone())
+            .header(RANGE, format!("bytes=0-{}", PROBE_SIZE - 1))
+    };
+    let resp = client.send(&url, request).await?;
+    if resp.status() != StatusCode::PARTIAL_CONTENT {
+        return Ok(Probe::Full(resp));
+    }
+
+    let head = resp.bytes().await?;
+    match imagesize::blob_size(&head) {
+        Ok(size) => Ok(Probe::Size(size)),
+        // e.g. jpeg with large exif before its frame header
+        Err(err) => {
+            debug!(
+                "no dimensions in first {} bytes of {}, {}",
+                PROBE_SIZE, url, err
+            );
+            Ok(Probe::Full(client.get(url).await?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::failure_class;
+    use crate::mock::{self, Response};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        // accepts but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let opts = ClientOptions {
+            read_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let client = CwaClient::build(host, &opts).unwrap();
+        let url = client.host.join("Data/x.js").unwrap();
+        let err = client.get(url).await.unwrap_err();
+        assert_eq!("timeout", failure_class(err.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // body trickles in faster than read timeout, never completes
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let head = "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n";
+                    stream.write_all(head.as_bytes()).await.unwrap();
+                    while stream.write_all(b"x").await.is_ok() {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                });
+            }
+        });
+
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let opts = ClientOptions {
+            read_timeout: Duration::from_secs(1),
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let client = CwaClient::build(host, &opts).unwrap();
+        let url = client.host.join("Data/x.png").unwrap();
+        let resp = client.get(url).await.unwrap();
+        let err = resp.bytes().await.unwrap_err();
+        assert!(err.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let addr = mock::serve(move |req| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if req.path.ends_with("missing.png") {
+                Response::new(404, "")
+            } else if n % 3 < 2 {
+                Response::new(502, "")
+            } else {
+                Response::new(200, "ok")
+            }
+        })
+        .await;
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let mut client = CwaClient::new(host);
+        client.retry = Retry {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+        };
+
+        // fails twice then succeeds
+        let url = client.host.join("Data/x.png").unwrap();
+        let resp = client.get(url.clone()).await.unwrap();
+        assert_eq!("ok", resp.text().await.unwrap());
+        assert_eq!(3, count.load(Ordering::SeqCst));
+
+        client.retry.retries = 1;
+        let err = client.get(url).await.unwrap_err();
+        assert_eq!("http", failure_class(err.as_ref()));
+        assert_eq!(5, count.load(Ordering::SeqCst));
+
+        // 4xx is not retried
+        count.store(0, Ordering::SeqCst);
+        let url = client.host.join("Data/missing.png").unwrap();
+        assert!(client.get(url).await.is_err());
+        assert_eq!(1, count.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let rate_limit = RateLimit::new(20.0);
+        let start = Instant::now();
+        rate_limit.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // shared by concurrent requests, spaced 50ms apart
+        let three = futures_util::future::join3(
+            rate_limit.acquire(),
+            rate_limit.acquire(),
+            rate_limit.acquire(),
+        );
+        three.await;
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(Duration::from_secs(2), RateLimit::new(0.5).interval);
+
+        assert_eq!(Ok(0.5), parse_rate("0.5"));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("inf").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        use tokio::io::AsyncReadExt;
+        let reader = |bandwidth| {
+            let chunks = (0..4).map(|_| Ok::<_, io::Error>(bytes::Bytes::from(vec![0; 1000])));
+            Throttle::new(
+                StreamReader::new(futures_util::stream::iter(chunks)),
+                bandwidth,
+            )
+        };
+
+        // 10KB/s shared by two readers of 4000 bytes, the last chunk is not waited
+        let bandwidth = Some(Arc::new(Bandwidth::new(10_000)));
+        let start = Instant::now();
+        let read = |mut reader: Throttle<_>| async move {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await.unwrap();
+            data.len()
+        };
+        let (a, b) = futures_util::future::join(
+            read(reader(bandwidth.clone())),
+            read(reader(bandwidth.clone())),
+        )
+        .await;
+        assert_eq!((4000, 4000), (a, b));
+        assert!(start.elapsed() >= Duration::from_millis(600));
+
+        let start = Instant::now();
+        assert_eq!(4000, read(reader(None)).await);
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = Retry {
+            retries: 3,
+            backoff: Duration::from_secs(1),
+        };
+        let secs = Duration::from_secs;
+        assert!((secs(1)..=secs(1) * 3 / 2).contains(&retry.delay(1)));
+        assert!((secs(4)..=secs(6)).contains(&retry.delay(3)));
+        // no overflow
+        retry.delay(100);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let (name, value) = parse_header("X-Forwarded-For: 10.0.0.1").unwrap();
+        assert_eq!("x-forwarded-for", name.as_str());
+        assert_eq!("10.0.0.1", value);
+        let (_, value) = parse_header("Referer:https://www.cwa.gov.tw/V8/C/").unwrap();
+        assert_eq!("https://www.cwa.gov.tw/V8/C/", value);
+
+        assert!(parse_header("X-Empty").is_err());
+        assert!(parse_header("Bad Name: x").is_err());
+        assert!(parse_header(": x").is_err());
+        assert!(parse_header("X-Line: a\nb").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_headers() {
+        let addr = mock::serve(|req| {
+            let header = |x: &str| req.headers.get(x).cloned().unwrap_or_default();
+            Response::new(
+                200,
+                format!("{}|{}", header("user-agent"), header("x-token")),
+            )
+        })
+        .await;
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let opts = ClientOptions {
+            user_agent: Some(parse_header_value("Mozilla/5.0").unwrap()),
+            headers: vec![parse_header("X-Token: abc").unwrap()],
+            ..Default::default()
+        };
+        let client = CwaClient::build(host, &opts).unwrap();
+        let url = client.host.join("Data/x.js").unwrap();
+        let body = client.get(url).await.unwrap().text().await.unwrap();
+        assert_eq!("Mozilla/5.0|abc", body);
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        assert_eq!(
+            "socks5h://127.0.0.1:1080",
+            parse_proxy("socks5h://127.0.0.1:1080").unwrap().as_str()
+        );
+        assert!(parse_proxy("http://proxy.local:3128").is_ok());
+        assert!(parse_proxy("127.0.0.1:1080").is_err());
+        assert!(parse_proxy("ftp://proxy.local").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        // plain http proxy gets the absolute url
+        let addr = mock::serve(|req| Response::new(200, req.path.clone())).await;
+        let proxy = parse_proxy(&format!("http://{}", addr)).unwrap();
+        check_proxy(&proxy, Duration::from_secs(1)).await.unwrap();
+        let opts = ClientOptions {
+            proxy: Some(proxy),
+            ..Default::default()
+        };
+        let client = CwaClient::build(crate::host::Host::default(), &opts).unwrap();
+        let url = Url::parse("http://www.cwa.gov.tw/Data/x.js").unwrap();
+        let body = client.get(url).await.unwrap().text().await.unwrap();
+        assert_eq!("http://www.cwa.gov.tw/Data/x.js", body);
+
+        // nothing listens on a closed port
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let proxy = parse_proxy(&format!("socks5h://127.0.0.1:{}", port)).unwrap();
+        let err = check_proxy(&proxy, Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(err.starts_with(&format!("cannot connect to proxy 127.0.0.1:{}", port)));
+    }
+}
@@ -0,0 +1,711 @@
+//! pruning old images of a task's dir
+use crate::dedupe;
+use crate::list_cache;
+use crate::manifest::{self, Manifest};
+use crate::marker::{self, JOURNAL};
+use crate::timestamp;
+use chrono::{DateTime, Local, NaiveDate};
+use log::info;
+#[cfg(test)]
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    /// by time in file name, mtime if none
+    pub max_age: Option<Duration>,
+    /// newest files to keep
+    pub keep_files: Option<usize>,
+    /// pruned images are moved here instead of deleted
+    pub trash: Option<Trash>,
+}
+
+/// deleted images are kept in `<dir>/<YYYY-MM-DD>/` of the day they were
+/// pruned, as `<name>.1`, `<name>.2`, ... when the day already has the name
+#[derive(Debug, Clone, Default)]
+pub struct Trash {
+    pub dir: PathBuf,
+    /// days older than this are deleted for good
+    pub max_age: Option<Duration>,
+}
+
+impl Trash {
+    /// move `path` into the dir of the day `now`, returns the new path
+    pub fn put(&self, path: &Path, now: SystemTime) -> io::Result<PathBuf> {
+        let day = self.dir.join(day_name(now));
+        std::fs::create_dir_all(&day)?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let mut to = day.join(&*name);
+        for i in 1.. {
+            if !to.exists() {
+                break;
+            }
+            to = day.join(format!("{}.{}", name, i));
+        }
+        move_file(path, &to)?;
+        Ok(to)
+    }
+
+    /// newest trashed copy of file `name`: of the latest day, then the
+    /// largest suffix
+    pub fn find(&self, name: &str) -> io::Result<Option<PathBuf>> {
+        let mut days = Vec::new();
+        match std::fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            entries => {
+                for entry in entries? {
+                    let entry = entry?;
+                    let day = entry.file_name().to_string_lossy().to_string();
+                    if is_day(&day) && entry.file_type()?.is_dir() {
+                        days.push(day);
+                    }
+                }
+            }
+        }
+        // day names sort by date
+        days.sort();
+
+        for day in days.iter().rev() {
+            let mut newest: Option<(u64, PathBuf)> = None;
+            for entry in std::fs::read_dir(self.dir.join(day))? {
+                let entry = entry?;
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let suffix = match file_name.strip_prefix(name) {
+                    Some("") => Some(0),
+                    Some(x) => x.strip_prefix('.').and_then(|x| x.parse().ok()),
+                    None => None,
+                };
+                if let Some(suffix) = suffix.filter(|x| newest.as_ref().is_none_or(|n| *x > n.0)) {
+                    newest = Some((suffix, entry.path()));
+                }
+            }
+            if let Some((_, path)) = newest {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// move the newest copy of file `name` back into `dir`, returns the path
+    /// it had in the trash. an existing file is never replaced
+    pub fn restore(&self, name: &str, dir: &Path) -> io::Result<PathBuf> {
+        let Some(trashed) = self.find(name)? else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in {}", name, self.dir.display()),
+            ));
+        };
+        let to = dir.join(name);
+        if to.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", to.display()),
+            ));
+        }
+        std::fs::create_dir_all(dir)?;
+        move_file(&trashed, &to)?;
+        Ok(trashed)
+    }
+
+    /// delete days older than `max_age`, returns deleted dirs
+    pub fn purge(&self, now: SystemTime) -> io::Result<Vec<PathBuf>> {
+        let Some(cutoff) = self.max_age.and_then(|x| now.checked_sub(x)) else {
+            return Ok(Vec::new());
+        };
+        let cutoff = day_name(cutoff);
+        let entries = match std::fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            x => x?,
+        };
+
+        let mut purged = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            // day names sort by date
+            if is_day(&name) && name < cutoff && entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+                purged.push(entry.path());
+            }
+        }
+        Ok(purged)
+    }
+}
+
+/// `YYYY-MM-DD` of `time` in local time
+fn day_name(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%Y-%m-%d").to_string()
+}
+
+/// dir of a day in the trash
+fn is_day(name: &str) -> bool {
+    NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// renames fail as if across file systems, the copy is then passed to
+    /// this before it is checked
+    static CROSS_DEVICE: Cell<Option<fn(&Path)>> = const { Cell::new(None) };
+}
+
+/// rename, or copy and delete when `to` is on another file system
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => copy_and_remove(from, to),
+        x => x,
+    }
+}
+
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(test)]
+    if CROSS_DEVICE.get().is_some() {
+        return Err(io::ErrorKind::CrossesDevices.into());
+    }
+    std::fs::rename(from, to)
+}
+
+/// delete `from` only once `to` has the same content
+fn copy_and_remove(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::copy(from, to)?;
+    #[cfg(test)]
+    if let Some(tamper) = CROSS_DEVICE.get() {
+        tamper(to);
+    }
+    if std::fs::read(from)? != std::fs::read(to)? {
+        let _ = std::fs::remove_file(to);
+        return Err(io::Error::other(format!(
+            "copy of {} to {} differs",
+            from.display(),
+            to.display()
+        )));
+    }
+    std::fs::remove_file(from)
+}
+
+impl Retention {
+    pub fn is_set(&self) -> bool {
+        self.max_age.is_some() || self.keep_files.is_some()
+    }
+
+    /// images older than this would be pruned right away
+    pub fn cutoff(&self, now: SystemTime) -> Option<SystemTime> {
+        self.max_age.and_then(|x| now.checked_sub(x))
+    }
+
+    /// files beyond the limits, oldest first
+    pub fn outdated(&self, mut files: Vec<(SystemTime, PathBuf)>, now: SystemTime) -> Vec<PathBuf> {
+        // newest first, ties by name for a stable result
+        files.sort_by(|a, b| b.cmp(a));
+        let cutoff = self.cutoff(now);
+        let keep = files
+            .iter()
+            .take(self.keep_files.unwrap_or(usize::MAX))
+            .take_while(|(time, _)| cutoff.is_none_or(|x| *time >= x))
+            .count();
+
+        files
+            .into_iter()
+            .skip(keep)
+            .rev()
+            .map(|(_, path)| path)
+            .collect()
+    }
+
+    /// delete outdated files of `dir` accepted by `eligible`, returns deleted
+    /// paths. `date_subdirs` also looks into `YYYY/MM/DD/`. images go to
+    /// `trash` if set, with the move recorded in `manifest`. partial files
+    /// are always deleted
+    pub fn prune(
+        &self,
+        dir: &Path,
+        date_subdirs: bool,
+        eligible: impl Fn(&str) -> bool,
+        manifest: Option<&Manifest>,
+        now: SystemTime,
+    ) -> io::Result<Vec<PathBuf>> {
+        if !self.is_set() {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        let mut partials = Vec::new();
+        match scan(dir, date_subdirs, &eligible, &mut files, &mut partials) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            x => x?,
+        }
+
+        let mut outdated = self.outdated(files.clone(), now);
+        let stale = self.stale_partials(&files, &outdated, partials, now);
+        for path in &outdated {
+            let trashed = remove(dir, path, self.trash.as_ref(), now)?;
+            if let (Some(manifest), Some(trashed)) = (manifest, trashed) {
+                manifest.trashed(dir, path, &trashed);
+            }
+        }
+        for path in &stale {
+            match std::fs::remove_file(partial_etag(path)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            remove(dir, path, None, now)?;
+        }
+        if let Some(trash) = &self.trash {
+            for path in trash.purge(now)? {
+                info!("emptied trash {}", path.display());
+            }
+        }
+        outdated.extend(stale);
+        Ok(outdated)
+    }
+
+    /// `.part` files of interrupted downloads not to be resumed: not written
+    /// for longer than `max_age`, or of an image older than all kept ones
+    /// when `keep_files` is reached
+    fn stale_partials(
+        &self,
+        files: &[(SystemTime, PathBuf)],
+        outdated: &[PathBuf],
+        partials: Vec<Partial>,
+        now: SystemTime,
+    ) -> Vec<PathBuf> {
+        let cutoff = self.cutoff(now);
+        let outdated: HashSet<&PathBuf> = outdated.iter().collect();
+        let oldest_kept = self.keep_files.filter(|x| files.len() >= *x).and_then(|_| {
+            files
+                .iter()
+                .filter(|(_, path)| !outdated.contains(path))
+                .map(|(time, _)| *time)
+                .min()
+        });
+
+        partials
+            .into_iter()
+            .filter(|x| {
+                cutoff.is_some_and(|cutoff| x.modified < cutoff)
+                    || oldest_kept.is_some_and(|kept| x.time < kept)
+            })
+            .map(|x| x.path)
+            .collect()
+    }
+}
+
+/// `<image>.part` left by an interrupted download
+#[derive(Debug)]
+struct Partial {
+    /// of the image, by its name or mtime of the part
+    time: SystemTime,
+    /// last written
+    modified: SystemTime,
+    path: PathBuf,
+}
+
+/// `<image>.part.etag` of `<image>.part`
+fn partial_etag(path: &Path) -> PathBuf {
+    let mut etag = path.as_os_str().to_owned();
+    etag.push(".etag");
+    PathBuf::from(etag)
+}
+
+/// images this tool could have saved and their partial files, never
+/// markers or links
+fn scan(
+    dir: &Path,
+    date_subdirs: bool,
+    eligible: &impl Fn(&str) -> bool,
+    files: &mut Vec<(SystemTime, PathBuf)>,
+    partials: &mut Vec<Partial>,
+) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if date_subdirs && is_date_dir(&name) {
+                scan(&entry.path(), date_subdirs, eligible, files, partials)?;
+            }
+            continue;
+        }
+
+        if let Some(image) = name.strip_suffix(".part") {
+            if file_type.is_file() && eligible(image) {
+                let metadata = entry.metadata()?;
+                partials.push(Partial {
+                    time: file_time(image, &metadata)?,
+                    modified: metadata.modified()?,
+                    path: entry.path(),
+                });
+            }
+            continue;
+        }
+        if !file_type.is_file()
+            || name == JOURNAL
+            || [
+                manifest::FILE_NAME,
+                list_cache::FILE_NAME,
+                dedupe::FILE_NAME,
+            ]
+            .contains(&name.as_str())
+            || name.starts_with("latest_")
+            || [".part", ".part.etag", ".done", ".tmp"]
+                .iter()
+                .any(|x| name.ends_with(x))
+            || !eligible(&name)
+        {
+            continue;
+        }
+        files.push((file_time(&name, &entry.metadata()?)?, entry.path()));
+    }
+    Ok(())
+}
+
+/// `YYYY`, `MM` or `DD` of `date_subdirs`
+fn is_date_dir(name: &str) -> bool {
+    matches!(name.len(), 2 | 4) && name.bytes().all(|x| x.is_ascii_digit())
+}
+
+/// time in file name, mtime if none
+fn file_time(name: &str, metadata: &Metadata) -> io::Result<SystemTime> {
+    match timestamp::parse(name) {
+        Some(time) => Ok(timestamp::to_system_time(time)),
+        None => metadata.modified(),
+    }
+}
+
+/// delete image, or move it to `trash`, with its `.done` marker, then date
+/// dirs left empty. returns the path in `trash`
+fn remove(
+    dir: &Path,
+    path: &Path,
+    trash: Option<&Trash>,
+    now: SystemTime,
+) -> io::Result<Option<PathBuf>> {
+    let trashed = match trash {
+        Some(trash) => Some(trash.put(path, now)?),
+        None => {
+            std::fs::remove_file(path)?;
+            None
+        }
+    };
+    match std::fs::remove_file(marker::done_path(path)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    let mut parent = path.parent();
+    while let Some(x) = parent.filter(|x| *x != dir) {
+        // fails if not empty
+        if std::fs::remove_dir(x).is_err() {
+            break;
+        }
+        parent = x.parent();
+    }
+    Ok(trashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn files() -> Vec<(SystemTime, PathBuf)> {
+        vec![
+            (at(300), PathBuf::from("c")),
+            (at(100), PathBuf::from("a")),
+            (at(200), PathBuf::from("b")),
+        ]
+    }
+
+    #[test]
+    fn test_outdated() {
+        let keep = |keep_files, max_age: Option<u64>| Retention {
+            keep_files,
+            max_age: max_age.map(Duration::from_secs),
+            ..Default::default()
+        };
+        let names = |x: Vec<PathBuf>| x.into_iter().map(|x| x.to_str().unwrap().to_string());
+
+        let outdated = keep(Some(1), None).outdated(files(), at(300));
+        assert_eq!(vec!["a", "b"], names(outdated).collect::<Vec<_>>());
+
+        let outdated = keep(None, Some(150)).outdated(files(), at(300));
+        assert_eq!(vec!["a"], names(outdated).collect::<Vec<_>>());
+
+        let outdated = keep(Some(5), Some(50)).outdated(files(), at(300));
+        assert_eq!(vec!["a", "b"], names(outdated).collect::<Vec<_>>());
+
+        assert!(keep(None, None).outdated(files(), at(300)).is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let day = dir.join("2024/07/24");
+        std::fs::create_dir_all(&day).unwrap();
+        std::fs::create_dir_all(dir.join("2024/07/25")).unwrap();
+        for name in [
+            "CV1_3600_202407241200.png",
+            "CV1_3600_202407241200.png.done",
+            "CV1_3600_202407241210.png",
+        ] {
+            std::fs::write(day.join(name), b"").unwrap();
+        }
+        let newest = dir.join("2024/07/25/CV1_3600_202407250000.png");
+        std::fs::write(&newest, b"").unwrap();
+        for name in [
+            "notes.txt",
+            "latest_radar.png",
+            "CV1_3600_202407260000.png.part",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let retention = Retention {
+            keep_files: Some(1),
+            ..Default::default()
+        };
+        let pruned = retention
+            .prune(
+                dir,
+                true,
+                |x| x.starts_with("CV1_3600"),
+                None,
+                SystemTime::now(),
+            )
+            .unwrap();
+        assert_eq!(2, pruned.len());
+        assert!(newest.exists());
+        // emptied date dir is removed, unrelated files stay
+        assert!(!dir.join("2024/07/24").exists());
+        for name in [
+            "notes.txt",
+            "latest_radar.png",
+            "CV1_3600_202407260000.png.part",
+        ] {
+            assert!(dir.join(name).exists());
+        }
+    }
+
+    #[test]
+    fn test_prune_partials() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let now = SystemTime::now();
+        let write = |name: &str, modified: SystemTime| {
+            let path = dir.join(name);
+            std::fs::write(&path, b"").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let hours = |x: u64| now - Duration::from_secs(x * 3600);
+        write("kept.png", hours(1));
+        // resumable, still written to
+        write("recent.png.part", hours(1));
+        write("recent.png.part.etag", hours(1));
+        // interrupted long ago, never retried
+        write("abandoned.png.part", hours(48));
+        write("abandoned.png.part.etag", hours(48));
+
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(24 * 3600)),
+            ..Default::default()
+        };
+        let pruned = retention.prune(dir, false, |_| true, None, now).unwrap();
+        assert_eq!(vec![dir.join("abandoned.png.part")], pruned);
+        assert!(!dir.join("abandoned.png.part.etag").exists());
+        for name in ["kept.png", "recent.png.part", "recent.png.part.etag"] {
+            assert!(dir.join(name).exists());
+        }
+
+        // of an image older than all kept ones
+        write("CV1_3600_202407241200.png", hours(1));
+        write("CV1_3600_202407241100.png.part", hours(1));
+        write("CV1_3600_202407241300.png.part", hours(1));
+        let retention = Retention {
+            keep_files: Some(1),
+            ..Default::default()
+        };
+        let pruned = retention
+            .prune(dir, false, |x| x.starts_with("CV1_"), None, now)
+            .unwrap();
+        assert_eq!(vec![dir.join("CV1_3600_202407241100.png.part")], pruned);
+        assert!(dir.join("CV1_3600_202407241300.png.part").exists());
+    }
+
+    #[test]
+    fn test_prune_to_trash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("images");
+        std::fs::create_dir(&dir).unwrap();
+        for name in [
+            "CV1_3600_202407241200.png",
+            "CV1_3600_202407241200.png.done",
+            "CV1_3600_202407241210.png",
+            "CV1_3600_202407241100.png.part",
+        ] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let now = SystemTime::now();
+        let trash = Trash {
+            dir: tmp.path().join("trash"),
+            max_age: Some(Duration::from_secs(7 * 86400)),
+        };
+        let old_day = trash.dir.join("2000-01-01");
+        std::fs::create_dir_all(&old_day).unwrap();
+        std::fs::write(old_day.join("CV1_3600_200001010000.png"), b"").unwrap();
+        let unrelated = trash.dir.join("notes");
+        std::fs::create_dir_all(&unrelated).unwrap();
+
+        let retention = Retention {
+            keep_files: Some(1),
+            trash: Some(trash.clone()),
+            ..Default::default()
+        };
+        let manifest = Manifest::default();
+        let pruned = retention
+            .prune(&dir, false, |_| true, Some(&manifest), now)
+            .unwrap();
+        assert_eq!(2, pruned.len());
+        // image is kept in the trash, marker and partial file are not
+        let moved = trash
+            .dir
+            .join(day_name(now))
+            .join("CV1_3600_202407241200.png");
+        assert_eq!(
+            "CV1_3600_202407241200.png",
+            std::fs::read_to_string(&moved).unwrap()
+        );
+        manifest.flush().unwrap();
+        let records = manifest::read_records(&dir).unwrap();
+        let [manifest::Record::Trashed(trashed)] = records.as_slice() else {
+            panic!("{:?}", records);
+        };
+        assert_eq!("CV1_3600_202407241200.png", trashed.file);
+        assert_eq!(moved.to_str().unwrap(), trashed.trash_path);
+        assert!(!dir.join("CV1_3600_202407241200.png.done").exists());
+        assert_eq!(
+            1,
+            std::fs::read_dir(trash.dir.join(day_name(now)))
+                .unwrap()
+                .count()
+        );
+        // old days are emptied, other dirs stay
+        assert!(!old_day.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[test]
+    fn test_trash_restore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("images");
+        std::fs::create_dir(&dir).unwrap();
+        let trash = Trash {
+            dir: tmp.path().join("trash"),
+            ..Default::default()
+        };
+        let name = "CV1_3600_202407241200.png";
+        let path = dir.join(name);
+
+        // same name on the same day is kept as another copy
+        let (day1, day2) = (at(86400 * 10), at(86400 * 11));
+        for (content, now) in [("first", day1), ("second", day2), ("third", day2)] {
+            std::fs::write(&path, content).unwrap();
+            trash.put(&path, now).unwrap();
+            assert!(!path.exists());
+        }
+        let day = trash.dir.join(day_name(day2));
+        assert_eq!("second", std::fs::read_to_string(day.join(name)).unwrap());
+        let newest = day.join(format!("{}.1", name));
+        assert_eq!("third", std::fs::read_to_string(&newest).unwrap());
+        assert_eq!(Some(newest.clone()), trash.find(name).unwrap());
+        assert_eq!(None, trash.find("CV1_3600_2024072412").unwrap());
+
+        // newest copy goes back, an existing file is never replaced
+        assert_eq!(newest, trash.restore(name, &dir).unwrap());
+        assert_eq!("third", std::fs::read_to_string(&path).unwrap());
+        assert!(trash.restore(name, &dir).is_err());
+        std::fs::remove_file(&path).unwrap();
+        trash.restore(name, &dir).unwrap();
+        assert_eq!("second", std::fs::read_to_string(&path).unwrap());
+        assert!(trash.restore("missing.png", &dir).is_err());
+    }
+
+    #[test]
+    fn test_put_cross_device() {
+        let tmp = tempfile::tempdir().unwrap();
+        let trash = Trash {
+            dir: tmp.path().join("trash"),
+            ..Default::default()
+        };
+        let path = tmp.path().join("CV1_3600_202407241200.png");
+        std::fs::write(&path, b"image").unwrap();
+
+        // copied, then deleted
+        CROSS_DEVICE.set(Some(|_| {}));
+        let moved = trash.put(&path, SystemTime::now()).unwrap();
+        assert!(!path.exists());
+        assert_eq!(b"image", std::fs::read(&moved).unwrap().as_slice());
+
+        // source is kept when the copy differs
+        std::fs::write(&path, b"image").unwrap();
+        CROSS_DEVICE.set(Some(|to| std::fs::write(to, b"imag").unwrap()));
+        assert!(trash.put(&path, SystemTime::now()).is_err());
+        CROSS_DEVICE.set(None);
+        assert_eq!(b"image", std::fs::read(&path).unwrap().as_slice());
+        assert_eq!(
+            1,
+            std::fs::read_dir(moved.parent().unwrap()).unwrap().count()
+        );
+    }
+
+    #[test]
+    fn test_prune_state_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let names = [
+            manifest::FILE_NAME,
+            list_cache::FILE_NAME,
+            dedupe::FILE_NAME,
+        ];
+        for name in names {
+            std::fs::write(tmp.path().join(name), b"{}").unwrap();
+        }
+        let retention = Retention {
+            keep_files: Some(0),
+            ..Default::default()
+        };
+        assert!(retention
+            .prune(tmp.path(), false, |_| true, None, SystemTime::now())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_prune_missing_dir() {
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let dir = Path::new("/nonexistent/cwa_images");
+        assert!(retention
+            .prune(dir, false, |_| true, None, SystemTime::now())
+            .unwrap()
+            .is_empty());
+    }
+}
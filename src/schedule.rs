@@ -0,0 +1,168 @@
+//! schedule of cycles, `--cron` in local time, per task intervals and `--jitter`
+use chrono::{DateTime, Local, TimeZone};
+use croner::Cron;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct Schedule(Cron);
+
+/// `--cron`, 5 fields or 6 with seconds first. e.g. `*/10 * * * *`
+pub fn parse(text: &str) -> Result<Schedule, String> {
+    let cron = Cron::new(text.trim())
+        .with_seconds_optional()
+        .parse()
+        .map_err(|err| format!("invalid cron expression {:?}: {}", text, err))?;
+    Ok(Schedule(cron))
+}
+
+impl Schedule {
+    /// first slot after `time`
+    pub fn next_after<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.0.find_next_occurrence(time, false).ok()
+    }
+
+    /// next slot to run after `last` slot. slots already passed at `now`,
+    /// e.g. by an overrunning cycle, are skipped and counted
+    pub fn next_slot<Tz: TimeZone>(
+        &self,
+        last: Option<&DateTime<Tz>>,
+        now: &DateTime<Tz>,
+    ) -> Option<(DateTime<Tz>, usize)> {
+        let mut skipped = 0;
+        if let Some(last) = last {
+            let mut slot = self.next_after(last)?;
+            while slot < *now {
+                skipped += 1;
+                slot = self.next_after(&slot)?;
+            }
+            return Some((slot, skipped));
+        }
+        Some((self.next_after(now)?, skipped))
+    }
+}
+
+/// next run of each task by its own interval, all due at start
+#[derive(Debug)]
+pub struct Timetable {
+    intervals: Vec<Duration>,
+    next: Vec<Instant>,
+}
+
+impl Timetable {
+    pub fn new(intervals: Vec<Duration>, start: Instant) -> Self {
+        let next = vec![start; intervals.len()];
+        Self { intervals, next }
+    }
+
+    /// earliest next run, none without task
+    pub fn next(&self) -> Option<Instant> {
+        self.next.iter().min().copied()
+    }
+
+    /// indexes of tasks due at `now`, their next run is moved by own
+    /// interval. runs missed by a long cycle are skipped
+    pub fn take_due(&mut self, now: Instant) -> Vec<usize> {
+        let mut due = Vec::new();
+        for (i, (next, interval)) in self.next.iter_mut().zip(&self.intervals).enumerate() {
+            if *next > now {
+                continue;
+            }
+            due.push(i);
+            while *next <= now && !interval.is_zero() {
+                *next += *interval;
+            }
+        }
+        due
+    }
+}
+
+/// time to sleep until `slot`, zero if passed
+pub fn until(slot: &DateTime<Local>) -> Duration {
+    (*slot - Local::now()).to_std().unwrap_or_default()
+}
+
+/// random delay of `0..=max`, millisecond precision
+pub fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(fastrand::u64(0..=max.as_millis() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, Utc};
+
+    fn time(s: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("*/10 * * * *").is_ok());
+        assert!(parse("30 */10 * * * *").is_ok());
+        assert!(parse("").is_err());
+        assert!(parse("every 10 minutes").is_err());
+        assert!(parse("61 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_timetable() {
+        let secs = Duration::from_secs;
+        let start = Instant::now();
+        let mut timetable = Timetable::new(vec![secs(120), secs(600)], start);
+        assert_eq!(Some(start), timetable.next());
+        assert_eq!(vec![0, 1], timetable.take_due(start));
+        assert!(timetable.take_due(start).is_empty());
+
+        // wakes for the shorter interval only
+        assert_eq!(Some(start + secs(120)), timetable.next());
+        assert_eq!(vec![0], timetable.take_due(start + secs(120)));
+        // overran 360s and 480s
+        assert_eq!(vec![0, 1], timetable.take_due(start + secs(610)));
+        assert_eq!(Some(start + secs(720)), timetable.next());
+
+        assert_eq!(None, Timetable::new(Vec::new(), start).next());
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(Duration::ZERO, jitter(Duration::ZERO));
+        let max = Duration::from_secs(5);
+        assert!((0..100).all(|_| jitter(max) <= max));
+    }
+
+    #[test]
+    fn test_next_slot() {
+        let schedule = parse("*/10 * * * *").unwrap();
+        let now = time("2024-07-24 12:03:00");
+        assert_eq!(
+            Some((time("2024-07-24 12:10:00"), 0)),
+            schedule.next_slot(None, &now)
+        );
+        // on the boundary runs at the next one
+        let now = time("2024-07-24 12:10:00");
+        assert_eq!(
+            Some((time("2024-07-24 12:20:00"), 0)),
+            schedule.next_slot(None, &now)
+        );
+
+        // finished in time
+        let last = time("2024-07-24 12:10:00");
+        let now = time("2024-07-24 12:12:00");
+        assert_eq!(
+            Some((time("2024-07-24 12:20:00"), 0)),
+            schedule.next_slot(Some(&last), &now)
+        );
+        // overran 12:20 and 12:30
+        let now = time("2024-07-24 12:31:00");
+        assert_eq!(
+            Some((time("2024-07-24 12:40:00"), 2)),
+            schedule.next_slot(Some(&last), &now)
+        );
+    }
+}
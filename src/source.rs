@@ -0,0 +1,187 @@
+//! catalog of built-in sources, presets are built from it
+use crate::task::Schema;
+use serde::Serialize;
+
+pub const OBSERVE_SAT_LIST: &str = "/Data/js/obs_img/Observe_sat.js";
+pub const OBSERVE_SAT_DIR: &str = "/Data/satellite/";
+
+pub const OBSERVE_RADAR_LIST: &str = "/Data/js/obs_img/Observe_radar.js";
+pub const OBSERVE_RADAR_DIR: &str = "/Data/radar/";
+
+pub const OBSERVE_RADAR_RAIN_LIST: &str = "/Data/js/obs_img/Observe_radar_rain.js";
+pub const OBSERVE_RADAR_RAIN_DIR: &str = "/Data/radar_rain/";
+
+pub const OBSERVE_LIGHTNING_LIST: &str = "/Data/js/obs_img/Observe_lightning.js";
+pub const OBSERVE_LIGHTNING_DIR: &str = "/Data/lightning/";
+
+pub const OBSERVE_RAINFALL_LIST: &str = "/Data/js/obs_img/Observe_rainfall.js";
+pub const OBSERVE_RAINFALL_DIR: &str = "/Data/rainfall/";
+
+pub const TYPHOON_LIST: &str = "/Data/js/typhoon/TY_LIST.js";
+pub const TYPHOON_DIR: &str = "/Data/typhoon/TY_NEWS/";
+
+pub const WEATHER_CHART_LIST: &str = "/Data/js/fcst_img/Weather_chart.js";
+pub const WEATHER_CHART_DIR: &str = "/Data/fcst_img/";
+
+pub const SURFACE_CHART_LIST: &str = "/Data/js/fcst_img/Surface_chart.js";
+pub const SURFACE_CHART_DIR: &str = "/Data/fcst_img/";
+
+/// built-in list and its image dir
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Source {
+    /// task name, also `--fetch-files --task`
+    pub name: &'static str,
+    pub list: &'static str,
+    pub dir: &'static str,
+    pub description: &'static str,
+    /// typical contains strings
+    pub examples: &'static [&'static str],
+    #[serde(skip)]
+    pub schema: Schema,
+}
+
+pub const SAT: Source = Source {
+    name: "sat",
+    list: OBSERVE_SAT_LIST,
+    dir: OBSERVE_SAT_DIR,
+    description: "satellite images",
+    examples: &["LCC_TRGB_2750", "LCC_IR1_CR_2750"],
+    schema: Schema::Img,
+};
+
+pub const RADAR: Source = Source {
+    name: "radar",
+    list: OBSERVE_RADAR_LIST,
+    dir: OBSERVE_RADAR_DIR,
+    description: "radar echo",
+    examples: &["CV1_3600", "CV1_TW_3600"],
+    schema: Schema::Img,
+};
+
+pub const RADAR_RAIN: Source = Source {
+    name: "radar_rain",
+    list: OBSERVE_RADAR_RAIN_LIST,
+    dir: OBSERVE_RADAR_RAIN_DIR,
+    description: "radar estimated rainfall",
+    examples: &["RCLY_3600", "RCLY_0900"],
+    schema: Schema::Img,
+};
+
+pub const LIGHTNING: Source = Source {
+    name: "lightning",
+    list: OBSERVE_LIGHTNING_LIST,
+    dir: OBSERVE_LIGHTNING_DIR,
+    description: "lightning observations",
+    examples: &[".lgtl."],
+    schema: Schema::Img,
+};
+
+pub const RAINFALL: Source = Source {
+    name: "rainfall",
+    list: OBSERVE_RAINFALL_LIST,
+    dir: OBSERVE_RAINFALL_DIR,
+    description: "accumulated rainfall maps",
+    examples: &[".QZT8.", ".QZJ8."],
+    schema: Schema::Img,
+};
+
+pub const TYPHOON: Source = Source {
+    name: "typhoon",
+    list: TYPHOON_LIST,
+    dir: TYPHOON_DIR,
+    description: "typhoon track potential, empty without storm",
+    examples: &["_PTA_"],
+    schema: Schema::Typhoon,
+};
+
+pub const WEATHER_CHART: Source = Source {
+    name: "weather_chart",
+    list: WEATHER_CHART_LIST,
+    dir: WEATHER_CHART_DIR,
+    description: "surface analysis and forecast charts",
+    examples: &["SFC_ANALYSIS", "FCST_24H"],
+    schema: Schema::Chart,
+};
+
+pub const SURFACE_CHART: Source = Source {
+    name: "surface_chart",
+    list: SURFACE_CHART_LIST,
+    dir: SURFACE_CHART_DIR,
+    description: "surface analysis charts, every 6 hours",
+    examples: &["SFCcombo"],
+    schema: Schema::Chart,
+};
+
+pub const SOURCES: &[Source] = &[
+    SAT,
+    RADAR,
+    RADAR_RAIN,
+    LIGHTNING,
+    RAINFALL,
+    TYPHOON,
+    WEATHER_CHART,
+    SURFACE_CHART,
+];
+
+pub fn find(name: &str) -> Option<&'static Source> {
+    SOURCES.iter().find(|x| x.name == name)
+}
+
+/// aligned columns of name, list, dir, examples and description
+pub fn table() -> String {
+    let rows: Vec<[String; 5]> = SOURCES
+        .iter()
+        .map(|x| {
+            [
+                x.name.to_string(),
+                x.list.to_string(),
+                x.dir.to_string(),
+                x.examples.join(", "),
+                x.description.to_string(),
+            ]
+        })
+        .collect();
+    let header = ["NAME", "LIST", "DIR", "EXAMPLES", "DESCRIPTION"].map(String::from);
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, table, SOURCES};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalog() {
+        let names: HashSet<&str> = SOURCES.iter().map(|x| x.name).collect();
+        assert_eq!(SOURCES.len(), names.len());
+        assert!(SOURCES.iter().all(|x| !x.examples.is_empty()));
+        assert_eq!("/Data/radar/", find("radar").unwrap().dir);
+        assert!(find("custom").is_none());
+
+        let table = table();
+        assert_eq!(SOURCES.len() + 1, table.lines().count());
+        assert!(table.starts_with("NAME "));
+        assert!(table.contains("radar_rain     /Data/js/obs_img/Observe_radar_rain.js"));
+
+        let json = serde_json::to_value(SOURCES).unwrap();
+        assert_eq!("sat", json[0]["name"]);
+        assert_eq!("LCC_TRGB_2750", json[0]["examples"][0]);
+    }
+}
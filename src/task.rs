@@ -0,0 +1,2829 @@
+//! tasks of an images list, and downloading their images
+use crate::budget::Budget;
+use crate::chart::Chart;
+use crate::dedupe::Dedupe;
+use crate::existing::{Existing, Force};
+#[cfg(feature = "faults")]
+use crate::fault;
+use crate::history::History;
+use crate::host::Host;
+use crate::link;
+use crate::list_cache::ListCache;
+use crate::manifest::Manifest;
+use crate::marker::Marker;
+use crate::matcher::{Filter, Matcher};
+use crate::metrics::{failure_class, Report};
+use crate::parser::{find_objects, find_objects_with, parse_source, schema_hints, CondKeys};
+use crate::refresh::Refresh;
+use crate::request::{self, status_of, CwaClient, Probe};
+use crate::retention::Retention;
+use crate::shutdown::Shutdown;
+use crate::source::{self, Source};
+use crate::timestamp::{self, Window};
+use crate::typhoon::Typhoon;
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::fs::{rename, File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
+use tokio::time::Instant;
+use url::Url;
+
+/// extensions of images retention may delete
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "webp"];
+
+/// cap of `--concurrency`, to be gentle to the site
+pub const MAX_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    pub allow_cross_host: bool,
+    pub ignore_key_case: bool,
+    pub history: Option<History>,
+    pub shutdown: Shutdown,
+    pub min_width: usize,
+    pub min_height: usize,
+    pub probe_dimensions: bool,
+    pub write_marker: Option<Marker>,
+    pub per_task_dirs: bool,
+    pub date_subdirs: bool,
+    pub latest_link: bool,
+    pub verify_image: bool,
+    pub verify_size: bool,
+    pub latest: Option<usize>,
+    pub window: Window,
+    pub existing: Existing,
+    pub list_cache: ListCache<Img>,
+    pub retention: Retention,
+    /// new files saved per task in a run, the rest waits for next runs
+    pub max_files: Option<usize>,
+    /// bytes saved by all tasks in a cycle, reset by the caller
+    pub max_bytes: Budget,
+    /// saved files of a run, written to `manifest.json` by `Manifest::flush`
+    pub manifest: Option<Manifest>,
+    /// images larger than this are skipped, unit: byte
+    pub max_size: Option<u64>,
+    /// download existing files again
+    pub force: Option<Force>,
+    pub refresh: Option<Refresh>,
+    pub dedupe: Option<Dedupe>,
+    pub order: Order,
+    /// downloads at a time, 0 is same as 1
+    pub concurrency: usize,
+    /// time to grow from 1 download at a time to `concurrency` in a run
+    pub ramp_up: Option<Duration>,
+    /// sizes in logs are 1000-based
+    pub si: bool,
+}
+
+/// order of downloads in a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Order {
+    /// as in the list, oldest first on CWA
+    #[default]
+    List,
+    /// newest first by time in file name
+    Newest,
+    /// oldest first by time in file name
+    Oldest,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CondKeys)]
+pub struct Img {
+    pub img: String,
+    pub text: String,
+    /// capture time from the entry, file name is used if none
+    #[serde(skip)]
+    pub time: Option<NaiveDateTime>,
+    /// other keys of the entry
+    #[serde(flatten)]
+    pub extra: Map<String, serde_json::Value>,
+}
+
+impl Img {
+    pub fn time(&self) -> Option<NaiveDateTime> {
+        self.time.or_else(|| timestamp::parse(&self.img))
+    }
+
+    pub async fn download(
+        &self,
+        client: &CwaClient,
+        dir: &str,
+    ) -> Result<reqwest::Response, Box<dyn Error>> {
+        client.get(self.url(&client.host, dir)?).await
+    }
+
+    pub fn url(&self, host: &Host, dir: &str) -> Result<Url, Box<dyn Error>> {
+        Ok(host.join(dir)?.join(&self.img)?)
+    }
+
+    /// none if `img` is empty, a dir (ends with `/` or `..`) or not utf-8
+    pub fn filename(&self) -> Option<&str> {
+        if self.img.ends_with('/') {
+            return None;
+        }
+        Path::new(&self.img).file_name()?.to_str()
+    }
+}
+
+/// result of fetching a single image
+#[derive(Debug, PartialEq)]
+pub enum Fetched {
+    Saved(usize),
+    Exists,
+    Rejected,
+    /// smaller than min dimensions
+    Filtered,
+    /// content is not an image, e.g. html error page
+    Invalid,
+    /// larger than `max_size`
+    TooLarge,
+}
+
+/// entry format of a list
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Schema {
+    #[default]
+    Img,
+    /// with chart type and valid time
+    Chart,
+    /// with storm name and issue time
+    Typhoon,
+}
+
+#[derive(Debug)]
+pub struct Task {
+    /// for logs and metrics
+    pub name: String,
+    pub list: String,
+    pub dir: String,
+    pub contains: Matcher,
+    pub exclude: Matcher,
+    /// `--contains-any`/`--contains-all`, on top of `contains`
+    pub filter: Filter,
+    /// substrings of `Img.text`, match any. empty is no filter
+    pub text_contains: Vec<String>,
+    /// own interval instead of `--interval`, checked on each slot of `--cron`
+    pub interval: Option<Duration>,
+    pub schema: Schema,
+    /// hint on the first list if nothing matched, e.g. of `--custom`
+    pub hint_empty: AtomicBool,
+    /// own download dir instead of the global one, `per_task_dirs` not applied
+    pub save_dir: Option<PathBuf>,
+}
+
+impl Task {
+    pub fn new(name: String, list: String, dir: String, contains: Matcher) -> Self {
+        Self {
+            name,
+            list,
+            dir,
+            contains,
+            exclude: Matcher::Contains(Vec::new()),
+            filter: Filter::default(),
+            text_contains: Vec::new(),
+            interval: None,
+            schema: Schema::Img,
+            hint_empty: AtomicBool::new(false),
+            save_dir: None,
+        }
+    }
+
+    /// task of a built-in source
+    pub fn from_source(source: &Source, contains: Matcher) -> Self {
+        Self {
+            schema: source.schema,
+            ..Self::new(
+                source.name.to_string(),
+                source.list.to_string(),
+                source.dir.to_string(),
+                contains,
+            )
+        }
+    }
+
+    pub fn new_sat(contains: Matcher) -> Self {
+        Self::from_source(&source::SAT, contains)
+    }
+
+    pub fn new_radar(contains: Matcher) -> Self {
+        Self::from_source(&source::RADAR, contains)
+    }
+
+    pub fn new_radar_rain(contains: Matcher) -> Self {
+        Self::from_source(&source::RADAR_RAIN, contains)
+    }
+
+    pub fn new_lightning(contains: Matcher) -> Self {
+        Self::from_source(&source::LIGHTNING, contains)
+    }
+
+    pub fn new_rainfall(contains: Matcher) -> Self {
+        Self::from_source(&source::RAINFALL, contains)
+    }
+
+    pub fn new_weather_chart(contains: Matcher) -> Self {
+        Self::from_source(&source::WEATHER_CHART, contains)
+    }
+
+    /// surface analysis only, same entry format as weather charts
+    pub fn new_surface_chart(contains: Matcher) -> Self {
+        Self::from_source(&source::SURFACE_CHART, contains)
+    }
+
+    pub fn new_typhoon(contains: Matcher) -> Self {
+        Self::from_source(&source::TYPHOON, contains)
+    }
+
+    /// built-in task by name
+    pub fn from_name(name: &str, contains: Matcher) -> Option<Self> {
+        source::find(name).map(|x| Self::from_source(x, contains))
+    }
+
+    pub async fn download_list(
+        &self,
+        client: &CwaClient,
+        opts: &RunOptions,
+    ) -> Result<Vec<Img>, Box<dyn Error>> {
+        info!("download list of {}", self.name);
+        let url = client.host.join(&self.list)?;
+        debug!("list url {}", url);
+        let request = || {
+            opts.list_cache
+                .conditional(url.as_str(), client.http.get(url.clone()))
+        };
+        let resp = client.send(&url, request).await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(imgs) = opts.list_cache.get(url.as_str()) {
+                debug!("list not modified");
+                return Ok(imgs);
+            }
+        }
+        let headers = resp.headers().clone();
+        let source = resp.text().await?;
+        let object = parse_source(&source)?;
+        let imgs: Vec<Img> = match self.schema {
+            Schema::Img if opts.ignore_key_case => find_objects_with(object.clone(), true),
+            Schema::Img => find_objects(object.clone()),
+            Schema::Chart => find_objects_with::<Chart>(object.clone(), opts.ignore_key_case)
+                .into_iter()
+                .map(Img::from)
+                .collect(),
+            Schema::Typhoon => find_objects_with::<Typhoon>(object.clone(), opts.ignore_key_case)
+                .into_iter()
+                .map(Img::from)
+                .collect(),
+        };
+        if imgs.is_empty() {
+            // list format may changed
+            let hints = schema_hints(&object, "img");
+            for keys in &hints {
+                warn!("no image matched, found object with keys {:?}", keys);
+            }
+            if hints.is_empty() && self.hint_empty.load(Ordering::Relaxed) {
+                warn!(
+                    "no image in {}, the list may be wrong or its structure unsupported, found {}",
+                    url,
+                    top_level(&object)
+                );
+            }
+        }
+        self.hint_empty.store(false, Ordering::Relaxed);
+        opts.list_cache.store(url.as_str(), &headers, &imgs);
+        Ok(imgs)
+    }
+
+    /// where images of this task are saved
+    pub fn out_dir(&self, base: &Path, opts: &RunOptions) -> PathBuf {
+        if let Some(dir) = &self.save_dir {
+            dir.clone()
+        } else if opts.per_task_dirs {
+            base.join(&self.name)
+        } else {
+            base.to_path_buf()
+        }
+    }
+
+    /// path of an image, under `YYYY/MM/DD/` of the time in its file name with
+    /// `date_subdirs`. files without time stay in the task's dir. `img` without
+    /// file name is skipped by [`Task::targets`] and fails in [`Task::fetch`]
+    pub fn dest(&self, base: &Path, img: &Img, opts: &RunOptions) -> PathBuf {
+        let mut dir = self.out_dir(base, opts);
+        if opts.date_subdirs {
+            if let Some(time) = timestamp::parse(&img.img) {
+                dir.push(time.format("%Y/%m/%d").to_string());
+            }
+        }
+        dir.join(img.filename().unwrap_or_default())
+    }
+
+    pub fn is_due(&self, last_run: Option<Instant>, now: Instant) -> bool {
+        match (self.interval, last_run) {
+            (Some(interval), Some(last_run)) => now.duration_since(last_run) >= interval,
+            _ => true,
+        }
+    }
+
+    pub fn is_match(&self, img: &Img) -> bool {
+        if !self.contains.is_match(&img.img) || !self.filter.is_match(&img.img) {
+            return false;
+        }
+
+        if self.exclude.is_match(&img.img) {
+            debug!("excluded {}", img.img);
+            return false;
+        }
+
+        true
+    }
+
+    /// description filter, file names are checked by `is_match`
+    pub fn is_text_match(&self, img: &Img) -> bool {
+        if self.text_contains.is_empty() || self.text_contains.iter().any(|x| img.text.contains(x))
+        {
+            return true;
+        }
+
+        debug!("skipped {}, text {:?} not matched", img.img, img.text);
+        false
+    }
+
+    /// all entries of the list with resolved url, grouped by list
+    pub async fn list_entries(
+        &self,
+        client: &CwaClient,
+        opts: &RunOptions,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        for img in self.download_list(client, opts).await? {
+            let url = img.url(&client.host, &self.dir)?;
+            let mut entry = json!({
+                "img": img.img,
+                "text": img.text,
+                "url": url.as_str(),
+            });
+            entry.as_object_mut().unwrap().extend(img.extra);
+            entries.push(entry);
+        }
+
+        Ok(json!({
+            "task": self.name,
+            "list": self.list,
+            "dir": self.dir,
+            "images": entries,
+        }))
+    }
+
+    /// url of `img`, none if it resolves outside of the task's dir
+    fn resolve(
+        &self,
+        host: &Host,
+        img: &Img,
+        opts: &RunOptions,
+    ) -> Result<Option<Url>, Box<dyn Error>> {
+        let base = host.join(&self.dir)?;
+        let url = img.url(host, &self.dir)?;
+        if !is_contained(&url, &base, opts.allow_cross_host) {
+            warn!("rejected {}, resolved outside of {}", url, base);
+            return Ok(None);
+        }
+        Ok(Some(url))
+    }
+
+    /// download a single image into `out_dir`, existing file is skipped
+    pub async fn fetch(
+        &self,
+        client: &CwaClient,
+        img: &Img,
+        out_dir: &Path,
+        opts: &RunOptions,
+    ) -> Result<Fetched, Box<dyn Error>> {
+        let Some(url) = self.resolve(&client.host, img, opts)? else {
+            return Ok(Fetched::Rejected);
+        };
+        if img.filename().is_none() {
+            return Err(format!("no file name in {:?}", img.img).into());
+        }
+
+        let dest = self.dest(out_dir, img, opts);
+        let overwrite = opts.existing.is_file(&dest);
+        // response of a file changed since saved, by `--refresh`
+        let mut changed = None;
+        // size differs from the server, by `--verify-size`
+        let mut truncated = false;
+        if overwrite && is_saved(&dest, opts) {
+            if opts.verify_size {
+                truncated = size_mismatch(client, &url, &dest).await?;
+            }
+            if let (false, Some(refresh)) = (truncated, &opts.refresh) {
+                changed = refresh_file(client, &url, &dest, refresh).await?;
+            }
+        }
+        // skip exists file
+        if overwrite && is_saved(&dest, opts) && changed.is_none() && !truncated {
+            debug!("skiped {}", dest.to_str().unwrap());
+            if let Some(marker) = opts.write_marker {
+                if !marker.is_marked(&dest).await {
+                    marker.mark(&dest).await?;
+                }
+            }
+            return Ok(Fetched::Exists);
+        } else if !overwrite && dest.exists() {
+            return Err(format!("{} is directory", dest.to_str().unwrap()).into());
+        }
+        // of the saved response, for `--refresh`
+        let mut headers = None;
+
+        // already downloaded body, when checked dimensions of full image
+        let mut body = None;
+        if opts.min_width > 0 || opts.min_height > 0 {
+            let size = if opts.probe_dimensions && changed.is_none() {
+                match request::probe(client, url.clone()).await? {
+                    Probe::Size(size) => size,
+                    Probe::Full(resp) => {
+                        debug!("range not supported, full download {}", img.img);
+                        if let Some(size) = oversized(&resp, opts.max_size) {
+                            return Ok(too_large(img, size, opts));
+                        }
+                        headers = Some(resp.headers().clone());
+                        let data = read_body(client, resp).await?;
+                        let size = imagesize::blob_size(&data)?;
+                        body = Some(data);
+                        size
+                    }
+                }
+            } else {
+                let resp = match changed.take() {
+                    Some(resp) => resp,
+                    None => img.download(client, &self.dir).await?,
+                };
+                if let Some(size) = oversized(&resp, opts.max_size) {
+                    return Ok(too_large(img, size, opts));
+                }
+                headers = Some(resp.headers().clone());
+                let data = read_body(client, resp).await?;
+                let size = imagesize::blob_size(&data)?;
+                body = Some(data);
+                size
+            };
+
+            if size.width < opts.min_width || size.height < opts.min_height {
+                debug!("filtered {} {}x{}", img.img, size.width, size.height);
+                return Ok(Fetched::Filtered);
+            }
+        }
+
+        let saved = match body {
+            Some(data) => {
+                if opts.max_size.is_some_and(|x| data.len() as u64 > x) {
+                    return Ok(too_large(img, data.len() as u64, opts));
+                }
+                if opts.verify_image && !is_image(&data) {
+                    warn!("{} is not an image, not saved", img.img);
+                    return Ok(Fetched::Invalid);
+                }
+                save_file(&dest, &mut data.as_slice()).await
+            }
+            None => {
+                // bytes in `<dest>.part` already
+                let (resp, offset) = match changed.take() {
+                    Some(resp) => (resp, 0),
+                    None => download_resume(client, &url, &dest).await?,
+                };
+                let max_size = opts.max_size.map(|x| x.saturating_sub(offset));
+                if let Some(size) = oversized(&resp, max_size) {
+                    discard_part(&dest);
+                    return Ok(too_large(img, offset + size, opts));
+                }
+                headers = Some(resp.headers().clone());
+                let etag = resp
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|x| x.to_str().ok())
+                    .map(|x| x.to_string());
+                // no Content-Length, or a server lying about it
+                let mut reader = SizeLimit::new(client.body(resp), max_size);
+                if opts.verify_image && offset > 0 {
+                    let head = read_head(&mut File::open(part_path(&dest, ".part")).await?).await?;
+                    if !is_image(&head) {
+                        warn!("{} is not an image, not saved", img.img);
+                        discard_part(&dest);
+                        return Ok(Fetched::Invalid);
+                    }
+                    save_part(&dest, &mut reader, etag.as_deref(), offset).await
+                } else if opts.verify_image {
+                    let head = read_head(&mut reader).await?;
+                    if !is_image(&head) {
+                        warn!("{} is not an image, not saved", img.img);
+                        return Ok(Fetched::Invalid);
+                    }
+                    let mut reader = head.as_slice().chain(reader);
+                    save_part(&dest, &mut reader, etag.as_deref(), offset).await
+                } else {
+                    save_part(&dest, &mut reader, etag.as_deref(), offset).await
+                }
+            }
+        };
+
+        match saved {
+            Ok(size) => {
+                let action = if overwrite { "overwrote" } else { "saved" };
+                info!(
+                    "{} {} {}",
+                    action,
+                    dest.to_str().unwrap(),
+                    human_size(size, opts.si)
+                );
+                opts.existing.insert(&dest);
+                if let Some(force) = &opts.force {
+                    force.done(&dest);
+                }
+                if let (Some(refresh), Some(headers)) = (&opts.refresh, &headers) {
+                    if let Err(err) = refresh.saved(&dest, headers) {
+                        warn!("cannot set mtime of {} {}", dest.to_str().unwrap(), err);
+                    }
+                }
+                if let Some(dedupe) = &opts.dedupe {
+                    match dedupe.link(&dest).await {
+                        Ok(Some(original)) => info!(
+                            "{} is the same as {}, linked",
+                            dest.to_str().unwrap(),
+                            original.to_str().unwrap()
+                        ),
+                        Ok(None) => {}
+                        Err(err) => warn!("cannot dedupe {} {}", dest.to_str().unwrap(), err),
+                    }
+                }
+                opts.max_bytes.add(size as u64);
+                if let Some(manifest) = &opts.manifest {
+                    let dir = self.out_dir(out_dir, opts);
+                    match tokio::fs::read(&dest).await {
+                        Ok(data) => manifest.record(&dir, &dest, &img.img, url.as_str(), &data),
+                        Err(err) => warn!("cannot hash {} {}", dest.to_str().unwrap(), err),
+                    }
+                }
+                if let Some(marker) = opts.write_marker {
+                    if let Err(err) = marker.mark(&dest).await {
+                        warn!("cannot write marker of {} {}", dest.to_str().unwrap(), err);
+                    }
+                }
+                Ok(Fetched::Saved(size))
+            }
+            Err(err) if is_too_large(err.as_ref()) => {
+                discard_part(&dest);
+                warn!(
+                    "aborted {}, larger than {}",
+                    img.img,
+                    human_size(opts.max_size.unwrap_or_default() as usize, opts.si)
+                );
+                Ok(Fetched::TooLarge)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// matched images of the list
+    pub async fn targets(
+        &self,
+        client: &CwaClient,
+        out_dir: &Path,
+        opts: &RunOptions,
+        limit: Option<usize>,
+    ) -> Result<Vec<Img>, Box<dyn Error>> {
+        let image_list = self.download_list(client, opts).await?;
+        if let Some(history) = &opts.history {
+            if let Err(err) = history.write(&self.list, &image_list) {
+                warn!("cannot write list history {}", err);
+            }
+        }
+        let mut targets: Vec<Img> = image_list
+            .into_iter()
+            .filter(|x| {
+                let named = x.filename().is_some();
+                if !named {
+                    warn!("skipped {:?} of {}, no file name", x.img, self.name);
+                }
+                named
+            })
+            .filter(|x| self.is_match(x) && self.is_text_match(x))
+            .filter(|x| in_window(x, &opts.window))
+            .collect();
+        if let Some(latest) = opts.latest {
+            keep_latest(&mut targets, latest);
+        }
+        // would be pruned right after download
+        if let Some(cutoff) = opts.retention.cutoff(SystemTime::now()) {
+            targets.retain(|x| {
+                x.time()
+                    .is_none_or(|x| timestamp::to_system_time(x) >= cutoff)
+            });
+        }
+        if let Some(keep_files) = opts.retention.keep_files {
+            keep_latest(&mut targets, keep_files);
+        }
+        if let Some(limit) = limit {
+            targets.retain(|x| !is_saved(&self.dest(out_dir, x, opts), opts));
+            let skipped = keep_newest(&mut targets, limit);
+            if skipped > 0 {
+                info!("catch-up limit reached, skipped {} older files", skipped);
+            }
+        }
+        sort_order(&mut targets, opts.order);
+
+        Ok(targets)
+    }
+
+    /// targets of the next run and whether each is already saved, nothing is
+    /// downloaded except the list
+    pub async fn dry_run(
+        &self,
+        client: &CwaClient,
+        out_dir: &Path,
+        opts: &RunOptions,
+        limit: Option<usize>,
+    ) -> Result<Vec<(Img, bool)>, Box<dyn Error>> {
+        let targets = self.targets(client, out_dir, opts, limit).await?;
+
+        Ok(targets
+            .into_iter()
+            .map(|x| {
+                let exists = is_saved(&self.dest(out_dir, &x, opts), opts);
+                (x, exists)
+            })
+            .collect())
+    }
+
+    /// urls of targets not saved yet, for another downloader. nothing is
+    /// downloaded except the list
+    pub async fn urls(
+        &self,
+        client: &CwaClient,
+        out_dir: &Path,
+        opts: &RunOptions,
+        limit: Option<usize>,
+    ) -> Result<Vec<Url>, Box<dyn Error>> {
+        let mut urls = Vec::new();
+        for (img, exists) in self.dry_run(client, out_dir, opts, limit).await? {
+            if exists {
+                continue;
+            }
+            if let Some(url) = self.resolve(&client.host, &img, opts)? {
+                urls.push(url);
+            }
+        }
+        Ok(urls)
+    }
+
+    /// count of `imgs` not saved yet
+    fn missing(&self, imgs: &[Img], out_dir: &Path, opts: &RunOptions) -> usize {
+        imgs.iter()
+            .filter(|x| !is_saved(&self.dest(out_dir, x, opts), opts))
+            .count()
+    }
+
+    /// point `latest_<name>` in the task's dir at `img`
+    pub async fn link_latest(&self, out_dir: &Path, img: &Img, opts: &RunOptions) {
+        let dest = self.dest(out_dir, img, opts);
+        let link = self
+            .out_dir(out_dir, opts)
+            .join(link::link_name(&self.name, &dest));
+        match link::update(&link, &dest).await {
+            Ok(()) => debug!("linked {} to {}", link.to_str().unwrap(), img.img),
+            Err(err) => warn!("cannot update {} {}", link.to_str().unwrap(), err),
+        }
+    }
+
+    /// delete images of this task beyond `opts.retention`, other files in the
+    /// dir are never touched. returns count of deleted files
+    pub fn prune(&self, out_dir: &Path, opts: &RunOptions) -> io::Result<usize> {
+        if self.contains.matches_all() {
+            return Err(io::Error::other(format!(
+                "{} matches every file, retention needs a filter of it",
+                self.name
+            )));
+        }
+        let eligible = |name: &str| self.is_own_image(name);
+        let pruned = opts.retention.prune(
+            &self.out_dir(out_dir, opts),
+            opts.date_subdirs,
+            eligible,
+            opts.manifest.as_ref(),
+            SystemTime::now(),
+        )?;
+        for path in &pruned {
+            info!("pruned {}", path.to_str().unwrap());
+        }
+        Ok(pruned.len())
+    }
+
+    /// file `name` is an image with time this task could have saved, by its
+    /// name or its path under `dir`
+    fn is_own_image(&self, name: &str) -> bool {
+        let extension = Path::new(name).extension().and_then(|x| x.to_str());
+        if !extension.is_some_and(|x| IMAGE_EXTENSIONS.contains(&x.to_ascii_lowercase().as_str()))
+            || timestamp::parse(name).is_none()
+        {
+            return false;
+        }
+
+        let img = |img: String| Img {
+            img,
+            ..Default::default()
+        };
+        let path = Host::default()
+            .join(&self.dir)
+            .and_then(|x| x.join(name))
+            .map(|x| x.path().to_string());
+        self.is_match(&img(name.to_string()))
+            || (!self.exclude.is_match(name) && path.is_ok_and(|x| self.is_match(&img(x))))
+    }
+
+    pub async fn run(
+        &self,
+        client: &CwaClient,
+        out_dir: &Path,
+        opts: &RunOptions,
+        limit: Option<usize>,
+    ) -> Result<Report, Box<dyn Error>> {
+        let targets = self.targets(client, out_dir, opts, limit).await?;
+        let mut reports = [Some(Report::default())];
+        let queue = targets.iter().map(|x| (0, x)).collect();
+        let downloaded = download(&[self], queue, &mut reports, client, out_dir, opts).await;
+        let [report] = reports;
+        let report = report.unwrap_or_default();
+
+        if downloaded.interrupted {
+            info!("shutting down, skip remaining files of {}", self.name);
+        }
+        if let (deferred @ 1.., limit) = downloaded.deferred[0] {
+            info!(
+                "{} reached, deferred {} files of {}",
+                limit, deferred, self.name
+            );
+        }
+
+        // link is untouched if nothing new
+        if let (true, Some(img)) = (opts.latest_link, downloaded.newest[0]) {
+            self.link_latest(out_dir, img, opts).await;
+        }
+
+        if let Err(err) = self.prune(out_dir, opts) {
+            warn!("cannot prune images of {} {}", self.name, err);
+        }
+
+        if let Some(rejected) = report.failures.get("rejected") {
+            warn!("{} entries of {} rejected", rejected, self.name);
+        }
+
+        Ok(report)
+    }
+}
+
+/// download matched images of all tasks as a single sequence sorted by time,
+/// returns report of each task, `None` if its list failed
+pub async fn run_merged(
+    tasks: &[&Task],
+    client: &CwaClient,
+    out_dir: &Path,
+    opts: &RunOptions,
+    limit: Option<usize>,
+) -> Vec<Option<Report>> {
+    let mut lists = Vec::new();
+    let mut reports = Vec::new();
+    for (i, task) in tasks.iter().enumerate() {
+        match task.targets(client, out_dir, opts, limit).await {
+            Ok(imgs) => {
+                lists.push((i, imgs));
+                reports.push(Some(Report::default()));
+            }
+            Err(err) => {
+                error!("{}", err);
+                reports.push(None);
+            }
+        }
+    }
+
+    let merged = merge_targets(lists, opts.order);
+    let queue = merged.iter().map(|(i, img)| (*i, img)).collect();
+    let downloaded = download(tasks, queue, &mut reports, client, out_dir, opts).await;
+    if downloaded.interrupted {
+        info!("shutting down, skip remaining files");
+    }
+
+    if opts.latest_link {
+        for (task, img) in tasks.iter().zip(downloaded.newest) {
+            if let Some(img) = img {
+                task.link_latest(out_dir, img, opts).await;
+            }
+        }
+    }
+
+    for (task, (deferred, limit)) in tasks.iter().zip(downloaded.deferred) {
+        if deferred > 0 {
+            info!(
+                "{} reached, deferred {} files of {}",
+                limit, deferred, task.name
+            );
+        }
+    }
+
+    for task in tasks {
+        if let Err(err) = task.prune(out_dir, opts) {
+            warn!("cannot prune images of {} {}", task.name, err);
+        }
+    }
+
+    reports
+}
+
+/// result of [`download`], by index of task
+struct Downloaded<'a> {
+    newest: Vec<Option<&'a Img>>,
+    /// count of files not started and the limit reached
+    deferred: Vec<(usize, &'static str)>,
+    /// stopped by shutdown
+    interrupted: bool,
+}
+
+/// fetch `queue` of task index and image, up to `opts.concurrency` at a time
+/// after `opts.ramp_up`. results are counted into `reports`, tasks without report are skipped
+async fn download<'a>(
+    tasks: &[&Task],
+    queue: Vec<(usize, &'a Img)>,
+    reports: &mut [Option<Report>],
+    client: &CwaClient,
+    out_dir: &Path,
+    opts: &RunOptions,
+) -> Downloaded<'a> {
+    let mut downloaded = Downloaded {
+        newest: vec![None; tasks.len()],
+        deferred: vec![(0, ""); tasks.len()],
+        interrupted: false,
+    };
+    let mut in_flight = vec![0; tasks.len()];
+    let mut pending = FuturesUnordered::new();
+    let mut queue = queue.into_iter().peekable();
+    let start = Instant::now();
+    loop {
+        let limit = ramp_limit(opts.concurrency, opts.ramp_up, start.elapsed());
+        while pending.len() < limit {
+            let Some(&(i, img)) = queue.peek() else {
+                break;
+            };
+            if opts.shutdown.is_requested() {
+                downloaded.interrupted = true;
+                break;
+            }
+            let Some(report) = &reports[i] else {
+                queue.next();
+                continue;
+            };
+            if let Some(limit) = limit_reached(report, in_flight[i], opts) {
+                // a running one may still fail and leave room
+                if in_flight[i] > 0 {
+                    break;
+                }
+                let deferred = &mut downloaded.deferred[i];
+                deferred.0 += tasks[i].missing(std::slice::from_ref(img), out_dir, opts);
+                deferred.1 = limit;
+                queue.next();
+                continue;
+            }
+
+            queue.next();
+            in_flight[i] += 1;
+            let task = tasks[i];
+            pending.push(async move { (i, img, task.fetch(client, img, out_dir, opts).await) });
+        }
+
+        let next = match ramp_step(opts.concurrency, opts.ramp_up, limit) {
+            Some(step) if queue.peek().is_some() => {
+                tokio::select! {
+                    next = pending.next() => next,
+                    _ = tokio::time::sleep_until(start + step) => continue,
+                }
+            }
+            _ => pending.next().await,
+        };
+        let Some((i, img, fetched)) = next else {
+            break;
+        };
+        in_flight[i] -= 1;
+        if let Some(report) = &mut reports[i] {
+            if count_fetched(report, img, fetched) {
+                keep_newer(&mut downloaded.newest[i], img);
+            }
+        }
+    }
+    downloaded
+}
+
+/// downloads allowed at a time `elapsed` into a run, growing evenly from 1 to
+/// `concurrency` over `ramp_up`
+fn ramp_limit(concurrency: usize, ramp_up: Option<Duration>, elapsed: Duration) -> usize {
+    let concurrency = concurrency.max(1);
+    match ramp_up {
+        Some(ramp_up) if elapsed < ramp_up => {
+            let grown = (concurrency - 1) as f64 * elapsed.as_secs_f64() / ramp_up.as_secs_f64();
+            (1 + grown as usize).min(concurrency)
+        }
+        _ => concurrency,
+    }
+}
+
+/// time into a run when [`ramp_limit`] grows past `limit`, none at full
+fn ramp_step(concurrency: usize, ramp_up: Option<Duration>, limit: usize) -> Option<Duration> {
+    let ramp_up = ramp_up?;
+    (limit < concurrency).then(|| ramp_up.mul_f64(limit as f64 / (concurrency - 1) as f64))
+}
+
+/// count result of a single image into `report`, returns true if it is newly
+/// saved. a failure is logged and does not stop the others
+fn count_fetched(report: &mut Report, img: &Img, fetched: Result<Fetched, Box<dyn Error>>) -> bool {
+    match fetched {
+        Ok(Fetched::Saved(size)) => {
+            report.saved += 1;
+            report.bytes += size as u64;
+            return true;
+        }
+        Ok(Fetched::Exists | Fetched::Filtered | Fetched::TooLarge) => {}
+        Ok(Fetched::Rejected) => report.fail("rejected"),
+        Ok(Fetched::Invalid) => report.fail("invalid"),
+        Err(err) => {
+            warn!("{}: {}", img.img, err);
+            report.fail(failure_class(err.as_ref()));
+        }
+    }
+    false
+}
+
+/// why no more downloads are started, `max_files` counts files of a task
+/// including `in_flight` ones and `max_bytes` all tasks
+fn limit_reached(report: &Report, in_flight: usize, opts: &RunOptions) -> Option<&'static str> {
+    if opts
+        .max_files
+        .is_some_and(|x| report.saved + in_flight as u64 >= x as u64)
+    {
+        Some("max files")
+    } else if opts.max_bytes.is_exhausted() {
+        Some("max bytes")
+    } else {
+        None
+    }
+}
+
+/// replace `newest` with `img` unless it is older by time, list order decides
+/// when either has no time
+fn keep_newer<'a>(newest: &mut Option<&'a Img>, img: &'a Img) {
+    let older = match (newest.and_then(|x| x.time()), img.time()) {
+        (Some(newest), Some(time)) => time < newest,
+        _ => false,
+    };
+    if !older {
+        *newest = Some(img);
+    }
+}
+
+/// merge images of tasks sorted by file name timestamp, duplicated file names
+/// are dropped. images without timestamp are placed last in list order
+fn merge_targets<T>(lists: Vec<(T, Vec<Img>)>, order: Order) -> Vec<(T, Img)>
+where
+    T: Copy,
+{
+    let mut merged: Vec<(T, Img)> = lists
+        .into_iter()
+        .flat_map(|(task, imgs)| imgs.into_iter().map(move |img| (task, img)))
+        .collect();
+    // stable sort, None is after any time
+    merged.sort_by(|(_, a), (_, b)| match (a.time(), b.time()) {
+        (Some(a), Some(b)) if order == Order::Newest => b.cmp(&a),
+        (a, b) => a.is_none().cmp(&b.is_none()).then(a.cmp(&b)),
+    });
+
+    let mut names = HashSet::new();
+    merged.retain(|(_, img)| names.insert(img.filename().unwrap_or(&img.img).to_string()));
+    merged
+}
+
+/// missing files a cycle may download, only the first (catch-up) cycle is
+/// limited
+#[derive(Debug)]
+pub struct Catchup {
+    limit: Option<usize>,
+    first: bool,
+}
+
+impl Catchup {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self { limit, first: true }
+    }
+
+    /// limit of the cycle about to run
+    pub fn next_limit(&mut self) -> Option<usize> {
+        if std::mem::take(&mut self.first) {
+            self.limit
+        } else {
+            None
+        }
+    }
+}
+
+/// keep last `limit` items (lists are oldest first), returns count of removed
+pub fn keep_newest<T>(items: &mut Vec<T>, limit: usize) -> usize {
+    let removed = items.len().saturating_sub(limit);
+    items.drain(..removed);
+    removed
+}
+
+/// frames without time are skipped when a window is given
+pub fn in_window(img: &Img, window: &Window) -> bool {
+    if !window.is_set() {
+        return true;
+    }
+    match img.time() {
+        Some(time) => window.contains(time),
+        None => {
+            debug!("skipped {}, no time in file name", img.img);
+            false
+        }
+    }
+}
+
+/// keep newest `n` images by file name timestamp, oldest first. falls back
+/// to list order if any timestamp is missing
+fn keep_latest(imgs: &mut Vec<Img>, n: usize) {
+    let times: Option<Vec<NaiveDateTime>> = imgs.iter().map(|x| x.time()).collect();
+    match times {
+        Some(times) => {
+            let mut sorted: Vec<(NaiveDateTime, Img)> =
+                times.into_iter().zip(imgs.drain(..)).collect();
+            sorted.sort_by_key(|(time, _)| *time);
+            imgs.extend(sorted.into_iter().map(|(_, img)| img));
+        }
+        None => warn!("some file names have no timestamp, --latest uses list order"),
+    }
+    keep_newest(imgs, n);
+}
+
+/// sort by file name timestamp for `order`. falls back to list order, or
+/// reversed for newest, if any timestamp is missing
+fn sort_order(imgs: &mut [Img], order: Order) {
+    if order == Order::List {
+        return;
+    }
+    if imgs.iter().all(|x| x.time().is_some()) {
+        imgs.sort_by_cached_key(|x| x.time());
+    } else {
+        warn!("some file names have no timestamp, --order uses list order");
+    }
+    if order == Order::Newest {
+        imgs.reverse();
+    }
+}
+
+/// Content-Length over `max_size`
+fn oversized(resp: &reqwest::Response, max_size: Option<u64>) -> Option<u64> {
+    let size = resp.content_length()?;
+    (size > max_size?).then_some(size)
+}
+
+fn too_large(img: &Img, size: u64, opts: &RunOptions) -> Fetched {
+    warn!(
+        "skipped {}, {} is too large",
+        img.img,
+        human_size(size as usize, opts.si)
+    );
+    Fetched::TooLarge
+}
+
+#[derive(Debug)]
+struct TooLarge;
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("larger than max size")
+    }
+}
+
+impl Error for TooLarge {}
+
+/// response of `dest` if changed since saved, none on 304
+async fn refresh_file(
+    client: &CwaClient,
+    url: &Url,
+    dest: &Path,
+    refresh: &Refresh,
+) -> Result<Option<reqwest::Response>, Box<dyn Error>> {
+    let request = || refresh.conditional(dest, client.http.get(url.clone()));
+    let resp = client.send(url, request).await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        debug!("not modified {}", dest.to_str().unwrap());
+        return Ok(None);
+    }
+    Ok(Some(resp))
+}
+
+/// `Content-Length` of a HEAD request differs from size of `dest`, false if
+/// the server has none
+async fn size_mismatch(client: &CwaClient, url: &Url, dest: &Path) -> Result<bool, Box<dyn Error>> {
+    let resp = client.send(url, || client.http.head(url.clone())).await?;
+    // not `content_length()`, which is of the empty body
+    let Some(expected) = resp
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok()?.parse::<u64>().ok())
+    else {
+        return Ok(false);
+    };
+    let size = std::fs::metadata(dest)?.len();
+    if size != expected {
+        warn!(
+            "{} is {} bytes but {} on the server, download again",
+            dest.to_str().unwrap(),
+            size,
+            expected
+        );
+    }
+    Ok(size != expected)
+}
+
+/// response of the image at `url`, a range request continuing `<dest>.part`
+/// of an interrupted download if any. `If-Range` with the ETag of its first
+/// response gets the whole image instead, if changed since. returns offset of
+/// the body
+async fn download_resume(
+    client: &CwaClient,
+    url: &Url,
+    dest: &Path,
+) -> Result<(reqwest::Response, u64), Box<dyn Error>> {
+    let Some((offset, etag)) = partial(dest) else {
+        return Ok((client.get(url.clone()).await?, 0));
+    };
+    let request = || {
+        client
+            .http
+            .get(url.clone())
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(IF_RANGE, &etag)
+    };
+    let resp = match client.send(url, request).await {
+        Ok(resp) => resp,
+        // e.g. part is complete already
+        Err(err) if status_of(err.as_ref()) == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+            discard_part(dest);
+            return Ok((client.get(url.clone()).await?, 0));
+        }
+        Err(err) => return Err(err),
+    };
+
+    let header = |name| resp.headers().get(name).and_then(|x| x.to_str().ok());
+    let continued = resp.status() == StatusCode::PARTIAL_CONTENT
+        && header(ETAG) == Some(etag.as_str())
+        && header(CONTENT_RANGE).is_some_and(|x| x.starts_with(&format!("bytes {}-", offset)));
+    if continued {
+        debug!("resume {} from {} bytes", url, offset);
+        return Ok((resp, offset));
+    }
+    discard_part(dest);
+    if resp.status() == StatusCode::OK {
+        // ranges not supported, or changed since
+        return Ok((resp, 0));
+    }
+    Ok((client.get(url.clone()).await?, 0))
+}
+
+/// whole body, throttled as any download
+async fn read_body(client: &CwaClient, resp: reqwest::Response) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    client.body(resp).read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+fn is_too_large(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .and_then(|x| x.get_ref())
+        .is_some_and(|x| x.is::<TooLarge>())
+}
+
+/// fails the read that goes past the limit, so the partial file is removed
+struct SizeLimit<R> {
+    inner: R,
+    remaining: Option<u64>,
+}
+
+impl<R> SizeLimit<R> {
+    fn new(inner: R, limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for SizeLimit<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - filled) as u64;
+        if let Some(remaining) = &mut self.remaining {
+            if read > *remaining {
+                return Poll::Ready(Err(io::Error::other(TooLarge)));
+            }
+            *remaining -= read;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// `dest` exists and is not to be downloaded again by `--force`
+fn is_saved(dest: &Path, opts: &RunOptions) -> bool {
+    opts.existing.is_file(dest) && !opts.force.as_ref().is_some_and(|x| x.is_pending(dest))
+}
+
+/// check the resolved url is still under the task's dir (and host)
+fn is_contained(url: &Url, base: &Url, allow_cross_host: bool) -> bool {
+    if !allow_cross_host && url.origin() != base.origin() {
+        return false;
+    }
+
+    url.path().starts_with(base.path())
+}
+
+/// `--custom-list` and `--custom-dir`, absolute paths on `host`. dir is
+/// normalized to end with `/`
+pub fn custom_paths(host: &Host, list: &str, dir: &str) -> Result<(String, String), String> {
+    let list = list.trim();
+    let mut dir = dir.trim().to_string();
+    let is_path = |x: &str| x.starts_with('/') && !x.starts_with("//");
+    if !is_path(list) {
+        return Err(format!(
+            "list {:?} must start with /, e.g. {}",
+            list,
+            source::OBSERVE_RADAR_LIST
+        ));
+    }
+    if list.ends_with('/') {
+        return Err(format!("list {:?} is a dir, expect a .js file", list));
+    }
+    if !is_path(&dir) {
+        return Err(format!(
+            "dir {:?} must start with /, e.g. {}",
+            dir,
+            source::OBSERVE_RADAR_DIR
+        ));
+    }
+    if !dir.ends_with('/') {
+        dir.push('/');
+    }
+
+    for path in [list, dir.as_str()] {
+        host.join(path)
+            .map_err(|err| format!("cannot join {:?} to {}: {}", path, host, err))?;
+    }
+    Ok((list.to_string(), dir))
+}
+
+/// what a list without images has at top level, for hints
+fn top_level(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(map) => {
+            format!("keys {:?}", map.keys().collect::<Vec<_>>())
+        }
+        serde_json::Value::Array(elems) => format!("an array of {} items", elems.len()),
+        _ => format!("a single value {}", value),
+    }
+}
+
+#[inline]
+pub fn check_dir(path: &Path) -> Result<(), std::io::Error> {
+    if path.is_dir() {
+        Ok(())
+    } else {
+        std::fs::create_dir_all(path)
+    }
+}
+
+#[inline]
+pub async fn save_file<R>(dest: &Path, reader: &mut R) -> Result<usize, Box<dyn Error>>
+where
+    R: AsyncRead + Unpin,
+{
+    save_part(dest, reader, None, 0).await
+}
+
+/// `save_file` appending to `<dest>.part` if `offset` is not 0. with `etag`
+/// of the response, the part is kept on error to be resumed later
+async fn save_part<R>(
+    dest: &Path,
+    reader: &mut R,
+    etag: Option<&str>,
+    offset: u64,
+) -> Result<usize, Box<dyn Error>>
+where
+    R: AsyncRead + Unpin,
+{
+    // subdirs are created on first write
+    if let Some(parent) = dest.parent() {
+        check_dir(parent)?;
+    }
+    // write to a sibling first, so dest is never a truncated image
+    let mut part = PartFile::new(dest);
+    let mut file = if offset > 0 {
+        OpenOptions::new().append(true).open(&part.path).await?
+    } else {
+        File::create(&part.path).await?
+    };
+    if let Some(etag) = etag {
+        if offset == 0 {
+            tokio::fs::write(part_path(dest, ".part.etag"), etag).await?;
+        }
+        part.keep = true;
+    }
+    // not `tokio::io::copy`, bytes it has read are lost on a read error
+    let mut buf = vec![0; 64 * 1024];
+    let mut writed = 0;
+    loop {
+        let read = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) => {
+                // complete for resume
+                file.flush().await?;
+                return Err(err.into());
+            }
+        };
+        file.write_all(&buf[..read]).await?;
+        writed += read as u64;
+    }
+    file.flush().await?;
+
+    #[cfg(feature = "faults")]
+    fault::rename()?;
+    rename(&part.path, dest).await?;
+    part.renamed = true;
+    Ok((offset + writed) as usize)
+}
+
+/// `<dest><suffix>`
+fn part_path(dest: &Path, suffix: &str) -> PathBuf {
+    let mut path = dest.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// size of `<dest>.part` and ETag of its response, left by an interrupted
+/// download
+fn partial(dest: &Path) -> Option<(u64, String)> {
+    let size = std::fs::metadata(part_path(dest, ".part")).ok()?.len();
+    let etag = std::fs::read_to_string(part_path(dest, ".part.etag")).ok()?;
+    (size > 0).then_some((size, etag))
+}
+
+fn discard_part(dest: &Path) {
+    let _ = std::fs::remove_file(part_path(dest, ".part"));
+    let _ = std::fs::remove_file(part_path(dest, ".part.etag"));
+}
+
+/// `<dest>.part`, removed on drop unless renamed or kept for resume. covers
+/// errors and cancelled downloads, e.g. by cycle timeout
+struct PartFile {
+    path: PathBuf,
+    dest: PathBuf,
+    renamed: bool,
+    keep: bool,
+}
+
+impl PartFile {
+    fn new(dest: &Path) -> Self {
+        Self {
+            path: part_path(dest, ".part"),
+            dest: dest.to_path_buf(),
+            renamed: false,
+            keep: false,
+        }
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if self.renamed || !self.keep {
+            discard_part(&self.dest);
+        }
+    }
+}
+
+/// enough leading bytes for [`is_image`]
+const MAGIC_SIZE: usize = 8;
+
+/// check magic bytes of jpeg, png and gif
+fn is_image(head: &[u8]) -> bool {
+    head.starts_with(&[0xff, 0xd8, 0xff])
+        || head.starts_with(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a])
+        || head.starts_with(b"GIF87a")
+        || head.starts_with(b"GIF89a")
+}
+
+/// read up to [`MAGIC_SIZE`] bytes, less only at the end of stream
+async fn read_head<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(MAGIC_SIZE);
+    reader
+        .take(MAGIC_SIZE as u64)
+        .read_to_end(&mut head)
+        .await?;
+    Ok(head)
+}
+
+/// e.g. `512B` or `1.50MB`, 1024-based or 1000-based with `si`
+pub fn human_size(size: usize, si: bool) -> String {
+    let base = if si { 1000.0 } else { 1024.0 };
+    let mut fsize = size as f64;
+    if fsize < base {
+        return format!("{}B", size);
+    }
+
+    let mut unit = 'K';
+    for u in ['K', 'M', 'G', 'T', 'P', 'E'] {
+        if fsize < base {
+            break;
+        }
+        fsize /= base;
+        unit = u;
+    }
+    format!("{:.2}{}B", fsize, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Mode;
+    use crate::mock::{self, Response};
+    use crate::request::{probe, PROBE_SIZE};
+    use crate::source::{OBSERVE_RADAR_DIR, OBSERVE_RADAR_LIST};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio_util::io::StreamReader;
+
+    const LIST: &str = r#"var data = {
+        "radar": [
+            {"img": "CV1_3600_202407241200.png", "text": "ok"},
+            {"img": "/Data/radar/CV1_3600_202407241210.png", "text": "absolute path in dir"},
+            {"img": "/../../other/path/file.png", "text": "escape"},
+            {"img": "../satellite/LCC_IR1.jpg", "text": "sibling dir"},
+            {"img": "//evil.example.com/Data/radar/x.png", "text": "protocol relative"},
+            {"img": "https://evil.example.com/Data/radar/y.png", "text": "other host"}
+        ]
+    }"#;
+
+    /// radar task of `CV1_` images, with a client of a mock server answering
+    /// `LIST` for the list and `image` for the rest
+    async fn radar_fixture<F>(image: F) -> (Task, CwaClient, std::net::SocketAddr)
+    where
+        F: Fn(&mock::Request) -> Response + Send + Sync + 'static,
+    {
+        let addr = mock::serve(move |req| {
+            if req.path.ends_with(".js") {
+                Response::new(200, LIST)
+            } else {
+                image(req)
+            }
+        })
+        .await;
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let task = Task::new_radar(Matcher::Contains(vec!["CV1_".to_string()]));
+        (task, CwaClient::new(host), addr)
+    }
+
+    fn resolve(allow_cross_host: bool) -> Vec<String> {
+        let host = Host::default();
+        let base = host.join(OBSERVE_RADAR_DIR).unwrap();
+        let imgs: Vec<Img> = find_objects(parse_source(LIST).unwrap());
+        imgs.into_iter()
+            .filter(|x| {
+                is_contained(
+                    &x.url(&host, OBSERVE_RADAR_DIR).unwrap(),
+                    &base,
+                    allow_cross_host,
+                )
+            })
+            .map(|x| x.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_custom_paths() {
+        let host = Host::default();
+        let paths = |list, dir| custom_paths(&host, list, dir);
+        assert_eq!(
+            Ok((OBSERVE_RADAR_LIST.into(), OBSERVE_RADAR_DIR.into())),
+            paths(OBSERVE_RADAR_LIST, OBSERVE_RADAR_DIR)
+        );
+        // trailing slash added
+        assert_eq!(
+            Ok((OBSERVE_RADAR_LIST.into(), OBSERVE_RADAR_DIR.into())),
+            paths(" /Data/js/obs_img/Observe_radar.js ", "/Data/radar")
+        );
+
+        assert!(paths("Data/js/obs_img/Observe_radar.js", "/Data/radar/").is_err());
+        assert!(paths(OBSERVE_RADAR_LIST, "Data/radar").is_err());
+        assert!(paths("//other/x.js", "/Data/radar/").is_err());
+        assert!(paths(OBSERVE_RADAR_LIST, "https://other/Data/").is_err());
+        assert!(paths("/Data/js/", "/Data/radar/").is_err());
+        assert!(paths("", "/").is_err());
+    }
+
+    #[test]
+    fn test_top_level() {
+        let value = parse_source("var list = {a: [], b: {c: 1}};").unwrap();
+        assert_eq!(r#"keys ["a", "b"]"#, top_level(&value));
+        let value = parse_source("var a = {}; var b = {};").unwrap();
+        assert_eq!("an array of 2 items", top_level(&value));
+    }
+
+    #[test]
+    fn test_url_guard() {
+        assert_eq!(vec!["ok", "absolute path in dir"], resolve(false));
+    }
+
+    #[test]
+    fn test_url_guard_cross_host() {
+        assert_eq!(
+            vec![
+                "ok",
+                "absolute path in dir",
+                "protocol relative",
+                "other host"
+            ],
+            resolve(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_file_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("frame.png");
+        let chunks = (0..4).map(|x| Ok::<_, std::io::Error>(bytes::Bytes::from(vec![x; 1000])));
+        let mut reader = StreamReader::new(futures_util::stream::iter(chunks));
+
+        assert_eq!(4000, save_file(&dest, &mut reader).await.unwrap());
+        let data = std::fs::read(&dest).unwrap();
+        assert_eq!(4000, data.len());
+        assert_eq!(3, data[3999]);
+    }
+
+    #[tokio::test]
+    async fn test_save_file_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("radar/2024-07-24/frame.png");
+        assert_eq!(4, save_file(&dest, &mut &b"data"[..]).await.unwrap());
+        assert_eq!(b"data", std::fs::read(&dest).unwrap().as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_save_file_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("frame.png");
+        let chunks = vec![
+            Ok(bytes::Bytes::from(vec![0; 1000])),
+            Err(std::io::Error::other("connection reset")),
+        ];
+        let mut reader = StreamReader::new(futures_util::stream::iter(chunks));
+
+        assert!(save_file(&dest, &mut reader).await.is_err());
+        // neither a truncated image nor the temp file is left
+        assert!(!dest.exists());
+        assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_save_file_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("frame.png");
+        // stalled after the first bytes
+        let (mut writer, mut reader) = tokio::io::duplex(64);
+        tokio::io::AsyncWriteExt::write_all(&mut writer, &[0; 16])
+            .await
+            .unwrap();
+
+        let saving = save_file(&dest, &mut reader);
+        let timeout = Duration::from_millis(100);
+        assert!(tokio::time::timeout(timeout, saving).await.is_err());
+        assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    /// image cut off after 1000 bytes on the first request, `ranges` on later ones
+    async fn flaky_server(ranges: bool) -> (std::net::SocketAddr, Arc<AtomicUsize>) {
+        let image: Vec<u8> = (0..2000).map(|x| x as u8).collect();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let addr = mock::serve(move |req| {
+            let etag = ("ETag", "\"v1\"");
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Response::new(200, image[..1000].to_vec())
+                    .header("Content-Length", "2000")
+                    .header(etag.0, etag.1);
+            }
+            let range = req.headers.get("range").map(|x| x.as_str());
+            match (ranges, range, req.headers.get("if-range")) {
+                (true, Some("bytes=1000-"), Some(tag)) if tag == etag.1 => {
+                    Response::new(206, image[1000..].to_vec())
+                        .header("Content-Range", "bytes 1000-1999/2000")
+                        .header(etag.0, etag.1)
+                }
+                _ => Response::new(200, image.clone()).header(etag.0, etag.1),
+            }
+        })
+        .await;
+        (addr, requests)
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        for ranges in [true, false] {
+            let (addr, requests) = flaky_server(ranges).await;
+            let dir = tempfile::tempdir().unwrap();
+            let opts = RunOptions::default();
+            let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+            let client = CwaClient::new(host);
+            let task = Task::new_radar(Matcher::Contains(Vec::new()));
+            let img = Img {
+                img: "CV1_3600_202407241200.png".to_string(),
+                ..Default::default()
+            };
+            let dest = dir.path().join(&img.img);
+
+            assert!(task.fetch(&client, &img, dir.path(), &opts).await.is_err());
+            assert_eq!(
+                1000,
+                std::fs::metadata(part_path(&dest, ".part")).unwrap().len()
+            );
+            assert_eq!(Some((1000, "\"v1\"".to_string())), partial(&dest));
+
+            let fetched = task.fetch(&client, &img, dir.path(), &opts).await;
+            assert_eq!(Fetched::Saved(2000), fetched.unwrap());
+            let data = std::fs::read(&dest).unwrap();
+            assert!(data.iter().enumerate().all(|(i, x)| *x == i as u8));
+            assert_eq!(2, requests.load(Ordering::SeqCst));
+            // only the image is left
+            assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+        }
+    }
+
+    /// png signature and IHDR chunk
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        data.extend_from_slice(&[0, 0, 0, 13]);
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        data
+    }
+
+    /// jpeg SOI, APP0 and SOF0 segments
+    fn jpeg_header(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10];
+        data.extend_from_slice(b"JFIF\0");
+        data.extend_from_slice(&[1, 1, 0, 0, 1, 0, 1, 0, 0]);
+        data.extend_from_slice(&[0xff, 0xc0, 0x00, 0x11, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_probe_dimensions() {
+        let addr = mock::serve(|req| {
+            let mut body = if req.path.ends_with(".png") {
+                png_header(3600, 3600)
+            } else if req.path.ends_with("exif.jpg") {
+                // APP1 segment larger than the probe before SOF0
+                let mut data = vec![0xff, 0xd8, 0xff, 0xe1, 0x50, 0x00];
+                data.resize(4 + 0x5000, 0);
+                data.extend_from_slice(&jpeg_header(2750, 2750)[2..]);
+                data
+            } else {
+                jpeg_header(2750, 2750)
+            };
+            // full image is much larger than the probe
+            body.resize(1024 * 1024, 0);
+
+            match req.headers.get("range") {
+                Some(range) if req.path.starts_with("/range/") => {
+                    assert_eq!("bytes=0-16383", range);
+                    let total = body.len();
+                    body.truncate(PROBE_SIZE);
+                    Response::new(206, body).header(
+                        "Content-Range",
+                        &format!("bytes 0-{}/{}", PROBE_SIZE - 1, total),
+                    )
+                }
+                _ => Response::new(200, body),
+            }
+        })
+        .await;
+        let client = CwaClient::default();
+        let url = |path: &str| Url::parse(&format!("http://{}{}", addr, path)).unwrap();
+
+        let Probe::Size(size) = probe(&client, url("/range/a.png")).await.unwrap() else {
+            panic!("expect partial content");
+        };
+        assert_eq!((3600, 3600), (size.width, size.height));
+
+        let Probe::Size(size) = probe(&client, url("/range/a.jpg")).await.unwrap() else {
+            panic!("expect partial content");
+        };
+        assert_eq!((2750, 2750), (size.width, size.height));
+
+        // range not supported, full response returned for download
+        let Probe::Full(resp) = probe(&client, url("/full/a.png")).await.unwrap() else {
+            panic!("expect full content");
+        };
+        assert_eq!(1024 * 1024, resp.bytes().await.unwrap().len());
+
+        // dimensions past the probe, full image downloaded instead
+        let Probe::Full(resp) = probe(&client, url("/range/exif.jpg")).await.unwrap() else {
+            panic!("expect full content");
+        };
+        let size = imagesize::blob_size(&resp.bytes().await.unwrap()).unwrap();
+        assert_eq!((2750, 2750), (size.width, size.height));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let images = Arc::new(AtomicUsize::new(0));
+        let counter = images.clone();
+        let (task, client, _) = radar_fixture(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::new(200, png_header(3600, 3600))
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("CV1_3600_202407241210.png"), "").unwrap();
+
+        let targets = task
+            .dry_run(&client, dir.path(), &RunOptions::default(), None)
+            .await
+            .unwrap();
+        let targets: Vec<(&str, bool)> = targets
+            .iter()
+            .map(|(x, y)| (x.filename().unwrap(), *y))
+            .collect();
+        assert_eq!(
+            vec![
+                ("CV1_3600_202407241200.png", false),
+                ("CV1_3600_202407241210.png", true)
+            ],
+            targets
+        );
+        assert_eq!(0, images.load(Ordering::SeqCst));
+        assert!(!dir.path().join("CV1_3600_202407241200.png").exists());
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!("0B", human_size(0, false));
+        assert_eq!("1023B", human_size(1023, false));
+        assert_eq!("1.00KB", human_size(1024, false));
+        assert_eq!("1.00MB", human_size(1024 * 1024, false));
+        assert_eq!("1.50TB", human_size(3 << 39, false));
+        assert_eq!("2.00PB", human_size(1 << 51, false));
+        assert_eq!("16.00EB", human_size(usize::MAX, false));
+
+        assert_eq!("999B", human_size(999, true));
+        assert_eq!("1.02KB", human_size(1024, true));
+        assert_eq!("1.50TB", human_size(1_500_000_000_000, true));
+    }
+
+    #[test]
+    fn test_filename() {
+        let filename = |img: &str| {
+            let img = Img {
+                img: img.to_string(),
+                ..Default::default()
+            };
+            img.filename().map(String::from)
+        };
+        assert_eq!(Some("b.png".into()), filename("/Data/a/b.png"));
+        assert_eq!(Some("b.png".into()), filename("b.png"));
+        assert_eq!(None, filename("/Data/radar/"));
+        assert_eq!(None, filename(""));
+        assert_eq!(None, filename("/Data/.."));
+    }
+
+    #[tokio::test]
+    async fn test_skip_no_filename() {
+        let list = r#"var data = [
+            {"img": "", "text": "empty"},
+            {"img": "CV1_3600/", "text": "dir"},
+            {"img": "CV1_3600_202407241200.png", "text": "ok"}
+        ]"#;
+        let addr = mock::serve(move |req| {
+            if req.path.ends_with(".js") {
+                Response::new(200, list)
+            } else {
+                Response::new(200, "image")
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_radar(Matcher::all());
+
+        let opts = RunOptions::default();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+        assert!(report.failures.is_empty());
+
+        let img = Img {
+            img: "CV1_3600/".into(),
+            ..Default::default()
+        };
+        assert!(task.fetch(&client, &img, dir.path(), &opts).await.is_err());
+    }
+
+    #[test]
+    fn test_img_url() {
+        let url = |dir: &str, img: &str| {
+            let img = Img {
+                img: img.to_string(),
+                ..Default::default()
+            };
+            img.url(&crate::host::parse("http://proxy/cwa").unwrap(), dir)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            "http://proxy/Data/radar/a.png",
+            url("/Data/radar/", "a.png")
+        );
+        assert_eq!(
+            "http://proxy/cwa/Data/radar/a.png",
+            url("Data/radar/", "a.png")
+        );
+        // dir without trailing slash is a file, replaced by img
+        assert_eq!("http://proxy/Data/a.png", url("/Data/radar", "a.png"));
+        assert_eq!("http://proxy/Data/a.png", url("/Data/radar/", "../a.png"));
+        assert_eq!(
+            "http://proxy/Data/b/a.png",
+            url("/Data/radar/", "/Data/b/a.png")
+        );
+        assert_eq!(
+            "http://proxy/Data/radar/a%20b.png?t=1",
+            url("/Data/radar/", "a b.png?t=1")
+        );
+        assert_eq!("http://other/a.png", url("/Data/radar/", "//other/a.png"));
+    }
+
+    #[tokio::test]
+    async fn test_urls() {
+        let images = Arc::new(AtomicUsize::new(0));
+        let counter = images.clone();
+        let (task, client, addr) = radar_fixture(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::new(200, png_header(3600, 3600))
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("CV1_3600_202407241210.png"), "").unwrap();
+
+        let urls = task
+            .urls(&client, dir.path(), &RunOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![format!(
+                "http://{}/Data/radar/CV1_3600_202407241200.png",
+                addr
+            )],
+            urls.iter().map(Url::to_string).collect::<Vec<_>>()
+        );
+        assert_eq!(0, images.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_date_subdirs() {
+        let addr = mock::serve(|_| Response::new(200, png_header(3600, 3600))).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            date_subdirs: true,
+            ..Default::default()
+        };
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_radar(Matcher::Contains(Vec::new()));
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            ..Default::default()
+        };
+
+        let dated = img("CV1_3600_202407241200.png");
+        let fetched = task.fetch(&client, &dated, dir.path(), &opts).await;
+        assert!(matches!(fetched, Ok(Fetched::Saved(_))));
+        let dest = dir.path().join("2024/07/24/CV1_3600_202407241200.png");
+        assert!(dest.is_file());
+        let fetched = task.fetch(&client, &dated, dir.path(), &opts).await;
+        assert_eq!(Fetched::Exists, fetched.unwrap());
+
+        // no time, flat dir
+        let fetched = task
+            .fetch(&client, &img("CV1_3600.png"), dir.path(), &opts)
+            .await;
+        assert!(matches!(fetched, Ok(Fetched::Saved(_))));
+        assert!(dir.path().join("CV1_3600.png").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_latest_link() {
+        let (task, client, _) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            latest_link: true,
+            ..Default::default()
+        };
+
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        let link = dir.path().join("latest_radar.png");
+        assert_eq!(
+            "/Data/radar/CV1_3600_202407241210.png",
+            std::fs::read_to_string(&link).unwrap()
+        );
+
+        // nothing new, link is left as is
+        std::fs::remove_file(&link).unwrap();
+        opts.existing.clear();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(0, report.saved);
+        assert!(!link.exists());
+    }
+
+    #[test]
+    fn test_prune_own_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = [
+            "CV1_3600_202407241200.png",
+            "CV1_3600_202407241210.png",
+            "CV2_3600_202407241100.png",
+            "CV1_3600_202407241100.txt",
+            "CV1_3600_latest.png",
+            "notes.txt",
+        ];
+        for name in names {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let opts = RunOptions {
+            retention: Retention {
+                keep_files: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // every file matches, nothing is touched
+        let task = Task::new_radar(Matcher::all());
+        assert!(task.prune(dir.path(), &opts).is_err());
+        assert_eq!(names.len(), std::fs::read_dir(dir.path()).unwrap().count());
+
+        // images with time in name only, by path under the task dir too
+        let regex = vec!["^/Data/radar/CV1_".to_string()];
+        let task = Task::new_radar(Matcher::new(regex, Mode::Regex).unwrap());
+        assert_eq!(1, task.prune(dir.path(), &opts).unwrap());
+        assert!(!dir.path().join("CV1_3600_202407241200.png").exists());
+        for name in &names[1..] {
+            assert!(dir.path().join(name).exists(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retention() {
+        let (task, client, _) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["CV1_3600_202407231200.png", "CV2_3600_202407231200.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let opts = RunOptions {
+            retention: Retention {
+                keep_files: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // older frame of the list would be pruned right away
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            vec!["CV1_3600_202407241210.png", "CV2_3600_202407231200.png"],
+            names
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catchup() {
+        let (task, client, _) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions::default();
+        let mut catchup = Catchup::new(Some(1));
+
+        // first cycle catches up the newest frame only, later ones the rest
+        let report = task
+            .run(&client, dir.path(), &opts, catchup.next_limit())
+            .await
+            .unwrap();
+        assert_eq!(1, report.saved);
+        assert!(dir.path().join("CV1_3600_202407241210.png").exists());
+        let report = task
+            .run(&client, dir.path(), &opts, catchup.next_limit())
+            .await
+            .unwrap();
+        assert_eq!(1, report.saved);
+        assert!(dir.path().join("CV1_3600_202407241200.png").exists());
+        assert_eq!(None, catchup.next_limit());
+    }
+
+    #[tokio::test]
+    async fn test_max_files() {
+        let (task, client, _) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            max_files: Some(1),
+            ..Default::default()
+        };
+
+        // deferred file is picked up by the next run
+        for saved in [1, 1, 0] {
+            opts.existing.clear();
+            let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+            assert_eq!(saved, report.saved);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let reports = run_merged(&[&task], &client, dir.path(), &opts, None).await;
+        assert_eq!(1, reports[0].as_ref().unwrap().saved);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency() {
+        let list: String = (0..8)
+            .map(|x| {
+                format!(
+                    r#"{{"img": "CV1_3600_2024072412{:02}.png", "text": ""}},"#,
+                    x
+                )
+            })
+            .collect();
+        let list = format!("var data = [{}]", list);
+        let addr = mock::serve(move |req| {
+            if req.path.ends_with(".js") {
+                Response::new(200, list.clone())
+            } else if req.path.ends_with("202407241203.png") {
+                Response::new(500, "")
+            } else {
+                Response::new(200, req.path.clone().into_bytes())
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            concurrency: 4,
+            ..Default::default()
+        };
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_radar(Matcher::all());
+
+        // a failure does not stop the others
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(7, report.saved);
+        assert_eq!(Some(&1), report.failures.get("http"));
+
+        // running ones count against max files
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            concurrency: 4,
+            max_files: Some(1),
+            ..Default::default()
+        };
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+        let reports = run_merged(&[&task], &client, dir.path(), &opts, None).await;
+        assert_eq!(1, reports[0].as_ref().unwrap().saved);
+    }
+
+    #[test]
+    fn test_ramp_limit() {
+        let ramp_up = Some(Duration::from_secs(30));
+        assert_eq!(1, ramp_limit(4, ramp_up, Duration::ZERO));
+        assert_eq!(1, ramp_limit(4, ramp_up, Duration::from_secs(9)));
+        assert_eq!(2, ramp_limit(4, ramp_up, Duration::from_secs(10)));
+        assert_eq!(3, ramp_limit(4, ramp_up, Duration::from_secs(29)));
+        assert_eq!(4, ramp_limit(4, ramp_up, Duration::from_secs(30)));
+        assert_eq!(4, ramp_limit(4, None, Duration::ZERO));
+        assert_eq!(1, ramp_limit(0, ramp_up, Duration::ZERO));
+
+        assert_eq!(Some(Duration::from_secs(10)), ramp_step(4, ramp_up, 1));
+        assert_eq!(Some(Duration::from_secs(20)), ramp_step(4, ramp_up, 2));
+        assert_eq!(None, ramp_step(4, ramp_up, 4));
+        assert_eq!(None, ramp_step(4, None, 1));
+    }
+
+    // the handler blocks its thread to keep downloads in flight
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_ramp_up() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (current, peak) = (in_flight.clone(), most.clone());
+        let addr = mock::serve(move |req| {
+            if req.path.ends_with(".js") {
+                let list: String = (0..4)
+                    .map(|x| {
+                        format!(
+                            r#"{{"img": "CV1_3600_2024072412{:02}.png", "text": ""}},"#,
+                            x
+                        )
+                    })
+                    .collect();
+                return Response::new(200, format!("var data = [{}]", list));
+            }
+            peak.fetch_max(current.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+            current.fetch_sub(1, Ordering::SeqCst);
+            Response::new(200, "")
+        })
+        .await;
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_radar(Matcher::all());
+
+        for (ramp_up, expected) in [(None, 3), (Some(Duration::from_secs(60)), 1)] {
+            most.store(0, Ordering::SeqCst);
+            let dir = tempfile::tempdir().unwrap();
+            let opts = RunOptions {
+                concurrency: 3,
+                ramp_up,
+                ..Default::default()
+            };
+            let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+            assert_eq!(4, report.saved);
+            assert_eq!(expected, most.load(Ordering::SeqCst), "{:?}", ramp_up);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_force() {
+        let (task, client, _) = radar_fixture(|_| Response::new(200, "fixed")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let truncated = dir.path().join("CV1_3600_202407241200.png");
+        std::fs::write(&truncated, "fix").unwrap();
+        let opts = RunOptions {
+            force: Some(Force::default()),
+            ..Default::default()
+        };
+
+        let urls = task.urls(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, urls.len());
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        assert_eq!("fixed", std::fs::read_to_string(&truncated).unwrap());
+
+        // overwritten once, not on every run
+        opts.existing.clear();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(0, report.saved);
+    }
+
+    #[tokio::test]
+    async fn test_dedupe() {
+        let (task, client, _) = radar_fixture(|_| Response::new(200, "reissued")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            dedupe: Some(Dedupe::new(dir.path().join(crate::dedupe::FILE_NAME))),
+            ..Default::default()
+        };
+
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        let first = dir.path().join("CV1_3600_202407241200.png");
+        let second = dir.path().join("CV1_3600_202407241210.png");
+        assert_eq!("reissued", std::fs::read_to_string(&second).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = std::fs::metadata(&first).unwrap();
+            assert_eq!(2, metadata.nlink());
+            assert_eq!(metadata.ino(), std::fs::metadata(&second).unwrap().ino());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_size() {
+        let heads = Arc::new(AtomicUsize::new(0));
+        let counter = heads.clone();
+        let (task, client, _) = radar_fixture(move |req| {
+            if req.method == "HEAD" {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            Response::new(200, "complete")
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let truncated = dir.path().join("CV1_3600_202407241200.png");
+        std::fs::write(&truncated, "comp").unwrap();
+        std::fs::write(dir.path().join("CV1_3600_202407241210.png"), "complete").unwrap();
+        let opts = RunOptions {
+            verify_size: true,
+            ..Default::default()
+        };
+
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+        assert_eq!(2, heads.load(Ordering::SeqCst));
+        assert_eq!("complete", std::fs::read_to_string(&truncated).unwrap());
+
+        // no HEAD without the flag
+        let report = task
+            .run(&client, dir.path(), &RunOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(0, report.saved);
+        assert_eq!(2, heads.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let version = Arc::new(AtomicUsize::new(1));
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let (current, counter) = (version.clone(), not_modified.clone());
+        let (task, client, _) = radar_fixture(move |req| {
+            let version = current.load(Ordering::SeqCst);
+            let etag = format!("\"v{}\"", version);
+            let last_modified = format!("Wed, 24 Jul 2024 1{}:00:00 GMT", version);
+            if req.headers.get("if-none-match") == Some(&etag)
+                || req.headers.get("if-modified-since") == Some(&last_modified)
+            {
+                counter.fetch_add(1, Ordering::SeqCst);
+                return Response::new(304, "");
+            }
+            Response::new(200, format!("v{}", version))
+                .header("ETag", &etag)
+                .header("Last-Modified", &last_modified)
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("CV1_3600_202407241200.png");
+        let opts = RunOptions {
+            refresh: Some(Refresh::default()),
+            ..Default::default()
+        };
+
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        let modified = std::fs::metadata(&saved).unwrap().modified().unwrap();
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1721818800),
+            modified
+        );
+
+        // by ETag, then by mtime after restart
+        opts.existing.clear();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(0, report.saved);
+        let restarted = RunOptions {
+            refresh: Some(Refresh::default()),
+            ..Default::default()
+        };
+        let report = task
+            .run(&client, dir.path(), &restarted, None)
+            .await
+            .unwrap();
+        assert_eq!(0, report.saved);
+        assert_eq!(4, not_modified.load(Ordering::SeqCst));
+
+        version.store(2, Ordering::SeqCst);
+        opts.existing.clear();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        assert_eq!("v2", std::fs::read_to_string(&saved).unwrap());
+
+        // no request for existing files without the flag
+        let report = task
+            .run(&client, dir.path(), &RunOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(0, report.saved);
+        assert_eq!(4, not_modified.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_max_bytes() {
+        let (task, client, _) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            max_bytes: Budget::new(Some(10)),
+            ..Default::default()
+        };
+
+        // budget is shared by tasks until reset
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+        assert_eq!(report.bytes, opts.max_bytes.used());
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(0, report.saved);
+
+        opts.max_bytes.reset();
+        opts.existing.clear();
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(1, report.saved);
+    }
+
+    #[tokio::test]
+    async fn test_manifest() {
+        let (task, client, addr) =
+            radar_fixture(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            per_task_dirs: true,
+            manifest: Some(Manifest::default()),
+            ..Default::default()
+        };
+
+        task.run(&client, dir.path(), &opts, None).await.unwrap();
+        opts.manifest.as_ref().unwrap().flush().unwrap();
+
+        let entries = crate::manifest::read(&dir.path().join("radar")).unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!("CV1_3600_202407241200.png", entries[0].img);
+        assert_eq!(
+            format!("http://{}/Data/radar/CV1_3600_202407241200.png", addr),
+            entries[0].url
+        );
+        assert_eq!("CV1_3600_202407241210.png", entries[1].file);
+        assert_eq!(
+            "/Data/radar/CV1_3600_202407241210.png".len() as u64,
+            entries[1].size
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_size() {
+        let addr = mock::serve(|req| {
+            let body = vec![0; 4096];
+            match req.path.as_str() {
+                "/Data/radar/fat.png" => Response::new(200, body),
+                "/Data/radar/stream.png" => Response::new(200, body).without_length(),
+                _ => Response::new(200, vec![0; 1024]).without_length(),
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            max_size: Some(2048),
+            ..Default::default()
+        };
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_radar(Matcher::Contains(Vec::new()));
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            ..Default::default()
+        };
+
+        for name in ["fat.png", "stream.png"] {
+            let fetched = task.fetch(&client, &img(name), dir.path(), &opts).await;
+            assert_eq!(Fetched::TooLarge, fetched.unwrap());
+            assert!(!dir.path().join(name).exists());
+            assert!(!dir.path().join(format!("{}.part", name)).exists());
+        }
+        let fetched = task
+            .fetch(&client, &img("small.png"), dir.path(), &opts)
+            .await;
+        assert_eq!(Fetched::Saved(1024), fetched.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_per_task_dirs() {
+        let addr = mock::serve(|req| Response::new(200, req.path.clone().into_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            per_task_dirs: true,
+            ..Default::default()
+        };
+        let client = CwaClient::default();
+        let img = Img {
+            img: "CV1_3600_202407241200.png".to_string(),
+            text: String::new(),
+            ..Default::default()
+        };
+
+        for name in ["radar", "mirror"] {
+            let task = Task::new(
+                name.to_string(),
+                String::new(),
+                format!("http://{}/{}/", addr, name),
+                Matcher::Contains(Vec::new()),
+            );
+            let fetched = task.fetch(&client, &img, dir.path(), &opts).await;
+            assert!(matches!(fetched, Ok(Fetched::Saved(_))));
+        }
+
+        // same file name, no longer overwritten
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name).join(&img.img));
+        assert_eq!("/radar/CV1_3600_202407241200.png", read("radar").unwrap());
+        assert_eq!("/mirror/CV1_3600_202407241200.png", read("mirror").unwrap());
+    }
+
+    #[test]
+    fn test_is_image() {
+        assert!(is_image(&jpeg_header(2750, 2750)));
+        assert!(is_image(&png_header(3600, 3600)));
+        assert!(is_image(b"GIF89a\x01\x00"));
+        assert!(!is_image(b"<!DOCTYPE html>"));
+        assert!(!is_image(&[0xff, 0xd8]));
+        assert!(!is_image(b""));
+    }
+
+    #[tokio::test]
+    async fn test_verify_image() {
+        let addr = mock::serve(|req| {
+            if req.path.ends_with(".png") {
+                Response::new(200, png_header(3600, 3600))
+            } else {
+                Response::new(200, "<html><body>503 Service Unavailable</body></html>")
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            verify_image: true,
+            ..Default::default()
+        };
+        let client = CwaClient::default();
+        let task = Task::new(
+            "radar".to_string(),
+            String::new(),
+            format!("http://{}/radar/", addr),
+            Matcher::Contains(Vec::new()),
+        );
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            text: String::new(),
+            ..Default::default()
+        };
+
+        let fetched = task.fetch(&client, &img("a.png"), dir.path(), &opts).await;
+        assert_eq!(Fetched::Saved(png_header(0, 0).len()), fetched.unwrap());
+        assert_eq!(
+            png_header(3600, 3600),
+            std::fs::read(dir.path().join("a.png")).unwrap()
+        );
+
+        let fetched = task.fetch(&client, &img("b.jpg"), dir.path(), &opts).await;
+        assert_eq!(Fetched::Invalid, fetched.unwrap());
+        assert!(!dir.path().join("b.jpg").exists());
+        assert!(!dir.path().join("b.jpg.part").exists());
+    }
+
+    #[test]
+    fn test_keep_latest() {
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            text: String::new(),
+            ..Default::default()
+        };
+        let names = |imgs: &[Img]| imgs.iter().map(|x| x.img.clone()).collect::<Vec<_>>();
+
+        // list is not in time order
+        let mut imgs = vec![
+            img("CV1_3600_202407241220.png"),
+            img("CV1_3600_202407241200.png"),
+            img("CV1_3600_202407241230.png"),
+            img("CV1_3600_202407241210.png"),
+        ];
+        keep_latest(&mut imgs, 3);
+        assert_eq!(
+            vec![
+                "CV1_3600_202407241210.png",
+                "CV1_3600_202407241220.png",
+                "CV1_3600_202407241230.png"
+            ],
+            names(&imgs)
+        );
+
+        // falls back to list order
+        let mut imgs = vec![
+            img("CV1_3600_202407241220.png"),
+            img("CV1_3600.png"),
+            img("CV1_3600_202407241200.png"),
+        ];
+        keep_latest(&mut imgs, 2);
+        assert_eq!(
+            vec!["CV1_3600.png", "CV1_3600_202407241200.png"],
+            names(&imgs)
+        );
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            ..Default::default()
+        };
+        let names = |imgs: &[Img]| imgs.iter().map(|x| x.img.clone()).collect::<Vec<_>>();
+        let list = vec![
+            img("CV1_3600_202407241220.png"),
+            img("CV1_3600_202407241200.png"),
+            img("CV1_3600_202407241210.png"),
+        ];
+
+        let mut imgs = list.clone();
+        sort_order(&mut imgs, Order::List);
+        assert_eq!(names(&list), names(&imgs));
+        sort_order(&mut imgs, Order::Newest);
+        assert_eq!(
+            vec![
+                "CV1_3600_202407241220.png",
+                "CV1_3600_202407241210.png",
+                "CV1_3600_202407241200.png"
+            ],
+            names(&imgs)
+        );
+        sort_order(&mut imgs, Order::Oldest);
+        assert_eq!("CV1_3600_202407241200.png", imgs[0].img);
+
+        // falls back to list order, reversed for newest
+        let mut imgs = vec![img("CV1_3600_202407241220.png"), img("CV1_3600.png")];
+        sort_order(&mut imgs, Order::Newest);
+        assert_eq!(
+            vec!["CV1_3600.png", "CV1_3600_202407241220.png"],
+            names(&imgs)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_not_modified() {
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let counter = not_modified.clone();
+        let addr =
+            mock::serve(
+                move |req| match req.headers.get("if-none-match").map(|x| x.as_str()) {
+                    Some("\"v1\"") => {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        Response::new(304, "")
+                    }
+                    _ => Response::new(200, LIST).header("ETag", "\"v1\""),
+                },
+            )
+            .await;
+        let opts = RunOptions::default();
+        let client = CwaClient::default();
+        let task = Task::new(
+            "radar".to_string(),
+            format!("http://{}/Observe_radar.js", addr),
+            OBSERVE_RADAR_DIR.to_string(),
+            Matcher::Contains(Vec::new()),
+        );
+
+        let first = task.download_list(&client, &opts).await.unwrap();
+        assert_eq!(6, first.len());
+        let second = task.download_list(&client, &opts).await.unwrap();
+        assert_eq!(1, not_modified.load(Ordering::SeqCst));
+        assert_eq!(
+            first.iter().map(|x| &x.img).collect::<Vec<_>>(),
+            second.iter().map(|x| &x.img).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_changed() {
+        let version = Arc::new(AtomicUsize::new(1));
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let (current, counter) = (version.clone(), not_modified.clone());
+        let addr = mock::serve(move |req| {
+            let etag = format!("\"v{}\"", current.load(Ordering::SeqCst));
+            if req.headers.get("if-none-match") == Some(&etag) {
+                counter.fetch_add(1, Ordering::SeqCst);
+                return Response::new(304, "");
+            }
+            let list = match etag.as_str() {
+                "\"v1\"" => LIST.to_string(),
+                _ => r#"var data = [{"img": "CV1_3600_202407241300.png", "text": ""}]"#.to_string(),
+            };
+            Response::new(200, list).header("ETag", &etag)
+        })
+        .await;
+        let opts = RunOptions::default();
+        let client = CwaClient::default();
+        let task = Task::new(
+            "radar".to_string(),
+            format!("http://{}/Observe_radar.js", addr),
+            OBSERVE_RADAR_DIR.to_string(),
+            Matcher::Contains(Vec::new()),
+        );
+
+        assert_eq!(6, task.download_list(&client, &opts).await.unwrap().len());
+        version.store(2, Ordering::SeqCst);
+        let changed = task.download_list(&client, &opts).await.unwrap();
+        assert_eq!(
+            vec!["CV1_3600_202407241300.png"],
+            changed.iter().map(|x| &x.img).collect::<Vec<_>>()
+        );
+        assert_eq!(0, not_modified.load(Ordering::SeqCst));
+        // the new list is cached in place of the old one
+        let cached = task.download_list(&client, &opts).await.unwrap();
+        assert_eq!(1, cached.len());
+        assert_eq!(1, not_modified.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_weather_chart() {
+        let addr = mock::serve(|req| {
+            if req.path.ends_with(".js") {
+                Response::new(200, include_str!("../testdata/Weather_chart.js"))
+            } else {
+                Response::new(200, jpeg_header(1024, 768))
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let opts = RunOptions {
+            latest: Some(2),
+            ..Default::default()
+        };
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_weather_chart(Matcher::Contains(vec!["SFC_".to_string()]));
+
+        let report = task.run(&client, dir.path(), &opts, None).await.unwrap();
+        assert_eq!(2, report.saved);
+        // newest by valid time, not list order
+        assert!(dir.path().join("SFC_ANALYSIS_01.jpg").is_file());
+        assert!(dir.path().join("SFC_ANALYSIS_02.jpg").is_file());
+        assert!(!dir.path().join("SFC_ANALYSIS_00.jpg").exists());
+
+        let list = task.list_entries(&client, &opts).await.unwrap();
+        let entry = &list["images"][0];
+        assert_eq!("FCST_24H.jpg", entry["img"]);
+        assert_eq!("FCST", entry["chart_type"]);
+        assert_eq!("2024-07-25 08:00", entry["valid_time"]);
+    }
+
+    #[test]
+    fn test_surface_chart_list() {
+        let value = parse_source(include_str!("../testdata/Surface_chart.js")).unwrap();
+        let imgs: Vec<Img> = find_objects_with::<Chart>(value, false)
+            .into_iter()
+            .map(Img::from)
+            .collect();
+        assert_eq!(3, imgs.len());
+
+        let task = Task::from_name(
+            "surface_chart",
+            Matcher::Contains(vec!["SFCcombo".to_string()]),
+        );
+        let task = task.unwrap();
+        assert_eq!(Schema::Chart, task.schema);
+        let mut matched: Vec<Img> = imgs.into_iter().filter(|x| task.is_match(x)).collect();
+        keep_latest(&mut matched, 1);
+        assert_eq!("SFCcombo_2024-07-24_0800.jpg", matched[0].img);
+        assert_eq!("SFC", matched[0].extra["chart_type"]);
+    }
+
+    #[tokio::test]
+    async fn test_typhoon_no_storm() {
+        let addr =
+            mock::serve(|_| Response::new(200, include_str!("../testdata/Typhoon_none.js"))).await;
+        let dir = tempfile::tempdir().unwrap();
+        let host = crate::host::parse(&format!("http://{}", addr)).unwrap();
+        let client = CwaClient::new(host);
+        let task = Task::new_typhoon(Matcher::Contains(vec!["TY_".to_string()]));
+
+        let report = task
+            .run(&client, dir.path(), &RunOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(0, report.saved);
+        assert!(report.failures.is_empty());
+    }
+
+    #[test]
+    fn test_rainfall_list() {
+        let value = parse_source(include_str!("../testdata/Observe_rainfall.js")).unwrap();
+        let imgs: Vec<Img> = find_objects(value);
+        assert_eq!(3, imgs.len());
+
+        let task = Task::from_name("rainfall", Matcher::Contains(vec![".QZT8.".to_string()]));
+        let task = task.unwrap();
+        let matched: Vec<&str> = imgs
+            .iter()
+            .filter(|x| task.is_match(x))
+            .filter_map(|x| x.filename())
+            .collect();
+        assert_eq!(vec!["2024-07-24_1130.QZT8.grd2.jpg"], matched);
+        assert_eq!(
+            "2024-07-24T11:30:00",
+            imgs[1].time().unwrap().format("%FT%T").to_string()
+        );
+    }
+
+    #[test]
+    fn test_lightning_list() {
+        let value = parse_source(include_str!("../testdata/Observe_lightning.js")).unwrap();
+        let imgs: Vec<Img> = find_objects(value);
+        assert_eq!(3, imgs.len());
+        assert!(imgs.iter().all(|x| x.extra.is_empty()));
+        assert_eq!("2024/07/24 08:00", imgs[1].text);
+        assert_eq!(
+            "2024-07-24T08:10:00",
+            imgs[2].time().unwrap().format("%FT%T").to_string()
+        );
+
+        let task = Task::from_name("lightning", Matcher::Contains(Vec::new())).unwrap();
+        assert_eq!(
+            "https://www.cwa.gov.tw/Data/lightning/2024-07-24_0750.lgtl.jpg",
+            imgs[0].url(&Host::default(), &task.dir).unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn test_merge_targets() {
+        let img = |name: &str| Img {
+            img: name.to_string(),
+            text: String::new(),
+            ..Default::default()
+        };
+        let sat = vec![
+            img("LCC_IR1_CR_2750-2024-07-24-12-00.jpg"),
+            img("LCC_IR1_CR_2750-2024-07-24-12-20.jpg"),
+            img("LCC_IR1_CR_2750.jpg"),
+        ];
+        let radar = vec![
+            img("CV1_3600_202407241210.png"),
+            img("CV1_3600_202407241230.png"),
+            img("/Data/radar/CV1_3600_202407241230.png"),
+        ];
+
+        let merged: Vec<(usize, String)> = merge_targets(vec![(0, sat), (1, radar)], Order::List)
+            .into_iter()
+            .map(|(task, img)| (task, img.img))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, "LCC_IR1_CR_2750-2024-07-24-12-00.jpg".to_string()),
+                (1, "CV1_3600_202407241210.png".to_string()),
+                (0, "LCC_IR1_CR_2750-2024-07-24-12-20.jpg".to_string()),
+                (1, "CV1_3600_202407241230.png".to_string()),
+                (0, "LCC_IR1_CR_2750.jpg".to_string()),
+            ],
+            merged
+        );
+
+        let sat = vec![
+            img("LCC_IR1_CR_2750.jpg"),
+            img("LCC_IR1_CR_2750-2024-07-24-12-00.jpg"),
+        ];
+        let radar = vec![img("CV1_3600_202407241210.png")];
+        let merged: Vec<String> = merge_targets(vec![(0, sat), (1, radar)], Order::Newest)
+            .into_iter()
+            .map(|(_, img)| img.img)
+            .collect();
+        assert_eq!(
+            vec![
+                "CV1_3600_202407241210.png",
+                "LCC_IR1_CR_2750-2024-07-24-12-00.jpg",
+                "LCC_IR1_CR_2750.jpg"
+            ],
+            merged
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! timestamp in image file names
+use chrono::{Days, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::Path;
+use std::time::SystemTime;
+
+lazy_static! {
+    /// `YYYYMMDDHHMM` (radar), `YYYY-MM-DD-HH-MM` (satellite) and
+    /// `YYYY-MM-DD_HHMM` (rainfall)
+    static ref TIMESTAMP: Regex = Regex::new(concat!(
+        r"(?:^|\D)(?:",
+        r"(\d{4})(\d{2})(\d{2})(\d{2})(\d{2})",
+        r"|(\d{4})-(\d{2})-(\d{2})[-_](\d{2})-?(\d{2})",
+        r")(?:\D|$)"
+    ))
+    .unwrap();
+}
+
+/// timestamp of image, only file name is used
+pub fn parse(img: &str) -> Option<NaiveDateTime> {
+    let name = Path::new(img).file_name()?.to_str()?;
+    let caps = TIMESTAMP.captures(name)?;
+    // first group of the matched form
+    let offset = if caps.get(1).is_some() { 1 } else { 6 };
+    let num = |i: usize| caps[offset + i].parse::<u32>().ok();
+
+    NaiveDate::from_ymd_opt(num(0)? as i32, num(1)?, num(2)?)?.and_hms_opt(num(3)?, num(4)?, 0)
+}
+
+/// file name times are Asia/Taipei, UTC+8 all year
+pub fn to_system_time(time: NaiveDateTime) -> SystemTime {
+    let taipei = FixedOffset::east_opt(8 * 3600).unwrap();
+    taipei.from_local_datetime(&time).unwrap().into()
+}
+
+/// time field of list entries, e.g. `2024-07-24 08:00`
+pub fn parse_time(text: &str) -> Option<NaiveDateTime> {
+    [
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y%m%d%H%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text.trim(), format).ok())
+}
+
+/// `--since`, date only is the start of the day. times in file names are
+/// Asia/Taipei local time, so are the bounds
+pub fn parse_since(text: &str) -> Result<NaiveDateTime, String> {
+    parse_bound(text, false)
+}
+
+/// `--until`, date only is the end of the day
+pub fn parse_until(text: &str) -> Result<NaiveDateTime, String> {
+    parse_bound(text, true)
+}
+
+fn parse_bound(text: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    if let Some(time) = parse_time(text) {
+        return Ok(time);
+    }
+
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("expect YYYY-MM-DD or YYYY-MM-DDTHH:MM, got {:?}", text))?;
+    let date = if end_of_day {
+        date.checked_add_days(Days::new(1))
+            .ok_or("date out of range")?
+    } else {
+        date
+    };
+    Ok(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// `since <= time < until`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Window {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl Window {
+    pub fn is_set(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    pub fn contains(&self, time: NaiveDateTime) -> bool {
+        self.since.is_none_or(|x| x <= time) && self.until.is_none_or(|x| time < x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::{parse, parse_since, parse_time, parse_until, to_system_time, Window};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn time(s: &str) -> Option<NaiveDateTime> {
+        Some(NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let expect = time("2024-07-24 12:30");
+        assert_eq!(expect, parse("CV1_3600_202407241230.png"));
+        assert_eq!(expect, parse("/Data/radar/CV1_TW_3600_202407241230.png"));
+        assert_eq!(expect, parse("LCC_IR1_CR_2750-2024-07-24-12-30.jpg"));
+        assert_eq!(expect, parse("2024-07-24_1230.QZJ8.grd2.jpg"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(None, parse("CV1_3600.png"));
+        assert_eq!(None, parse("CV1_3600_202413241230.png"));
+        assert_eq!(None, parse("CV1_3600_2024072412301.png"));
+        // timestamp in dir is ignored
+        assert_eq!(None, parse("/202407241230/latest.png"));
+    }
+
+    #[test]
+    fn test_to_system_time() {
+        // 2024-07-24 04:30 UTC
+        let expect = UNIX_EPOCH + Duration::from_secs(1721795400);
+        assert_eq!(expect, to_system_time(time("2024-07-24 12:30").unwrap()));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let expect = time("2024-07-24 08:00");
+        assert_eq!(expect, parse_time("2024-07-24 08:00"));
+        assert_eq!(expect, parse_time("2024/07/24 08:00"));
+        assert_eq!(expect, parse_time("202407240800"));
+        assert_eq!(None, parse_time("07/24 08:00"));
+    }
+
+    #[test]
+    fn test_window() {
+        assert_eq!(
+            time("2024-07-24 00:00").unwrap(),
+            parse_since("2024-07-24").unwrap()
+        );
+        assert_eq!(
+            time("2024-07-25 00:00").unwrap(),
+            parse_until("2024-07-24").unwrap()
+        );
+        assert_eq!(
+            time("2024-07-25 12:00").unwrap(),
+            parse_until("2024-07-25T12:00").unwrap()
+        );
+        assert!(parse_since("07-24").is_err());
+
+        let window = Window {
+            since: Some(parse_since("2024-07-24T00:00").unwrap()),
+            until: Some(parse_until("2024-07-25T12:00").unwrap()),
+        };
+        assert!(window.is_set());
+        assert!(window.contains(time("2024-07-24 00:00").unwrap()));
+        assert!(window.contains(time("2024-07-25 11:50").unwrap()));
+        assert!(!window.contains(time("2024-07-25 12:00").unwrap()));
+        assert!(!window.contains(time("2024-07-23 23:50").unwrap()));
+
+        let open = Window::default();
+        assert!(!open.is_set());
+        assert!(open.contains(time("2024-07-23 23:50").unwrap()));
+    }
+}
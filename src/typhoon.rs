@@ -0,0 +1,81 @@
+//! typhoon track potential list entries, the list is an empty object when
+//! there is no storm
+use crate::parser::CondKeys;
+use crate::task::Img;
+use crate::timestamp;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// track image of a storm
+#[derive(Debug, Deserialize, CondKeys)]
+pub struct Typhoon {
+    pub img: String,
+    pub text: String,
+    /// international name, e.g. `GAEMI`
+    #[serde(default, rename = "typhoonName")]
+    #[condkeys(skip)]
+    pub name: Option<String>,
+    /// e.g. `2024-07-24 08:00`
+    #[serde(default, rename = "issueTime")]
+    #[condkeys(skip)]
+    pub issue_time: Option<String>,
+    /// other keys, e.g. chinese name and warning type
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl From<Typhoon> for Img {
+    fn from(typhoon: Typhoon) -> Self {
+        let mut extra = typhoon.extra;
+        if let Some(name) = typhoon.name {
+            extra.insert("typhoon_name".into(), Value::String(name));
+        }
+        let time = typhoon
+            .issue_time
+            .as_deref()
+            .and_then(timestamp::parse_time);
+        if let Some(issue_time) = typhoon.issue_time {
+            extra.insert("issue_time".into(), Value::String(issue_time));
+        }
+
+        Img {
+            img: typhoon.img,
+            text: typhoon.text,
+            time,
+            extra,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Typhoon;
+    use crate::parser::{find_objects, parse_source};
+    use crate::task::Img;
+
+    #[test]
+    fn test_parse_typhoons() {
+        let source = include_str!("../testdata/Typhoon.js");
+        let typhoons: Vec<Typhoon> = find_objects(parse_source(source).unwrap());
+        assert_eq!(3, typhoons.len());
+
+        let imgs: Vec<Img> = typhoons.into_iter().map(Img::from).collect();
+        assert_eq!("TY_GAEMI_PTA_202407241400.png", imgs[1].img);
+        assert_eq!("GAEMI", imgs[1].extra["typhoon_name"]);
+        assert_eq!("2024-07-24 14:00", imgs[1].extra["issue_time"]);
+        assert_eq!("凱米", imgs[1].extra["cwaTyphoonName"]);
+        assert_eq!("SEA_LAND", imgs[1].extra["warning"]);
+        assert_eq!(
+            "2024-07-24 14:00",
+            imgs[1].time().unwrap().format("%Y-%m-%d %H:%M").to_string()
+        );
+        assert!(!imgs[2].extra.contains_key("warning"));
+    }
+
+    #[test]
+    fn test_no_storm() {
+        let source = include_str!("../testdata/Typhoon_none.js");
+        let typhoons: Vec<Typhoon> = find_objects(parse_source(source).unwrap());
+        assert!(typhoons.is_empty());
+    }
+}
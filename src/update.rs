@@ -0,0 +1,79 @@
+//! check newer release, never auto update
+use log::{debug, info, warn};
+use reqwest::Client;
+use semver::Version;
+use serde_json::Value;
+use std::error::Error;
+
+/// find version field of release json, both github `tag_name` and plain
+/// `version` are accepted
+fn release_version(body: &str) -> Result<Version, Box<dyn Error>> {
+    let value: Value = serde_json::from_str(body)?;
+    let tag = ["tag_name", "version"]
+        .iter()
+        .find_map(|x| value.get(*x).and_then(Value::as_str))
+        .ok_or("version field not found")?;
+
+    Ok(Version::parse(tag.trim_start_matches('v'))?)
+}
+
+/// newer release version than `current`, if any
+fn newer_version(body: &str, current: &str) -> Result<Option<Version>, Box<dyn Error>> {
+    let latest = release_version(body)?;
+    let current = Version::parse(current)?;
+
+    Ok((latest > current).then_some(latest))
+}
+
+async fn fetch_release(client: &Client, url: &str) -> Result<String, Box<dyn Error>> {
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+pub async fn check(client: Client, url: String) {
+    let current = env!("CARGO_PKG_VERSION");
+    let result = fetch_release(&client, &url)
+        .await
+        .and_then(|body| newer_version(&body, current));
+
+    match result {
+        Ok(Some(version)) => info!("new version {} available, current {}", version, current),
+        Ok(None) => debug!("already latest version"),
+        Err(err) => warn!("update check failed: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::newer_version;
+
+    const RELEASE: &str = r#"{
+        "tag_name": "v0.3.0",
+        "name": "v0.3.0",
+        "assets": []
+    }"#;
+
+    #[test]
+    fn test_newer_version() {
+        let version = newer_version(RELEASE, "0.2.0").unwrap().unwrap();
+        assert_eq!("0.3.0", version.to_string());
+
+        assert!(newer_version(RELEASE, "0.3.0").unwrap().is_none());
+        assert!(newer_version(RELEASE, "1.0.0").unwrap().is_none());
+        assert!(newer_version(r#"{"version": "0.2.1"}"#, "0.2.0")
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_bad_release() {
+        assert!(newer_version(r#"{"name": "latest"}"#, "0.2.0").is_err());
+        assert!(newer_version(r#"{"tag_name": "nightly"}"#, "0.2.0").is_err());
+        assert!(newer_version("<html></html>", "0.2.0").is_err());
+    }
+}
@@ -0,0 +1,8 @@
+var Observe_lightning = {
+    "lightning": [
+        {"img": "2024-07-24_0750.lgtl.jpg", "text": "2024/07/24 07:50"},
+        {"img": "2024-07-24_0800.lgtl.jpg", "text": "2024/07/24 08:00"},
+        {"img": "2024-07-24_0810.lgtl.jpg", "text": "2024/07/24 08:10"}
+    ],
+    "legend": {"src": "lightning_legend.png"}
+};
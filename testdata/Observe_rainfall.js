@@ -0,0 +1,11 @@
+var Observe_rainfall = {
+    "QZJ8": [
+        {"img": "2024-07-24_1130.QZJ8.grd2.jpg", "text": "今日累積雨量 2024/07/24 11:30"}
+    ],
+    "QZT8": [
+        {"img": "2024-07-24_1130.QZT8.grd2.jpg", "text": "24小時累積雨量 2024/07/24 11:30"}
+    ],
+    "QZ38": [
+        {"img": "2024-07-24_1130.QZ38.grd2.jpg", "text": "3小時累積雨量 2024/07/24 11:30"}
+    ]
+};
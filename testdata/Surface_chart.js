@@ -0,0 +1,7 @@
+var SurfaceChart = {
+    "surface": [
+        {"img": "SFCcombo_2024-07-24_0200.jpg", "text": "地面天氣圖 07/24 02:00", "type": "SFC", "validTime": "2024-07-24 02:00"},
+        {"img": "SFCcombo_2024-07-24_0800.jpg", "text": "地面天氣圖 07/24 08:00", "type": "SFC", "validTime": "2024-07-24 08:00"},
+        {"img": "SFCcolor_2024-07-24_0800.jpg", "text": "彩色地面天氣圖 07/24 08:00", "type": "SFC", "validTime": "2024-07-24 08:00"}
+    ]
+};
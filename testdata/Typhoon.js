@@ -0,0 +1,9 @@
+var TY_LIST = {
+    "GAEMI": [
+        {"img": "TY_GAEMI_PTA_202407240800.png", "text": "颱風路徑潛勢預報 07/24 08:00", "typhoonName": "GAEMI", "cwaTyphoonName": "凱米", "issueTime": "2024-07-24 08:00", "warning": "SEA_LAND"},
+        {"img": "TY_GAEMI_PTA_202407241400.png", "text": "颱風路徑潛勢預報 07/24 14:00", "typhoonName": "GAEMI", "cwaTyphoonName": "凱米", "issueTime": "2024-07-24 14:00", "warning": "SEA_LAND"}
+    ],
+    "PRAPIROON": [
+        {"img": "TY_PRAPIROON_PTA_202407241400.png", "text": "颱風路徑潛勢預報 07/24 14:00", "typhoonName": "PRAPIROON", "cwaTyphoonName": "派比安", "issueTime": "2024-07-24 14:00"}
+    ]
+};
@@ -0,0 +1 @@
+var TY_LIST = {};
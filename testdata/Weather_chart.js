@@ -0,0 +1,14 @@
+var WeatherChart = {
+    "surface": [
+        {"img": "SFC_ANALYSIS_01.jpg", "text": "地面天氣圖 07/24 08:00", "type": "SFC", "validTime": "2024-07-24 08:00"},
+        {"img": "SFC_ANALYSIS_02.jpg", "text": "地面天氣圖 07/24 14:00", "type": "SFC", "validTime": "2024-07-24 14:00"},
+        {"img": "SFC_ANALYSIS_00.jpg", "text": "地面天氣圖 07/24 02:00", "type": "SFC", "validTime": "2024-07-24 02:00"}
+    ],
+    "forecast": [
+        {"img": "FCST_24H.jpg", "text": "24小時預測圖", "type": "FCST", "validTime": "2024-07-25 08:00"},
+        {"img": "FCST_48H.jpg", "text": "48小時預測圖", "type": "FCST", "validTime": "2024-07-26 08:00"}
+    ],
+    "upper": [
+        {"img": "UPPER_500HPA.jpg", "text": "500百帕高空圖", "type": "UPPER"}
+    ]
+};